@@ -0,0 +1,207 @@
+//! Columns of tables, for hashing only some of them.
+use rusqlite::{
+    Connection, Row,
+    types::{ToSqlOutput, ValueRef},
+};
+
+/// Which columns of a table are hashed, besides the columns excluded by name.
+///
+/// # Examples
+/// ```no_run
+/// # use sqlite_dbhash::{ColumnPolicy, HashOptions};
+/// # use rusqlite::Connection;
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let conn = Connection::open("my_db.db")?;
+///     // Generated columns are derived from the others
+///     let hash = HashOptions::new()
+///         .column_policy(ColumnPolicy::ExcludeGenerated)
+///         .hash(&conn)?;
+///     println!("{hash}");
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColumnPolicy {
+    /// The columns returned by `SELECT *`, like the original dbhash utility
+    /// program: generated columns, both `VIRTUAL` and `STORED`, are hashed,
+    /// while `HIDDEN` columns of virtual tables are not.
+    #[default]
+    Visible,
+    /// Leave out generated columns, since they are derived from the others,
+    /// as well as `HIDDEN` columns.
+    ExcludeGenerated,
+    /// Hash every column listed by `PRAGMA table_xinfo`, including `HIDDEN`
+    /// columns of virtual tables.
+    ///
+    /// The hidden columns of full-text search tables whose value depends on
+    /// the query reading them are still left out, so that the hash is stable:
+    /// the column named after the table, and the `rank` column of FTS5.
+    IncludeHidden,
+}
+
+/// A column left out of the hash, in one table or in all of them.
+#[derive(Clone, Debug)]
+pub(crate) struct ColumnExclusion {
+    /// Name of the table, or `None` for all tables
+    pub(crate) table: Option<String>,
+    pub(crate) column: String,
+}
+
+impl ColumnExclusion {
+    /// Whether `column` of `table` is excluded.
+    pub(crate) fn excludes(&self, table: &[u8], column: &[u8]) -> bool {
+        self.applies_to(table) && self.column.as_bytes().eq_ignore_ascii_case(column)
+    }
+
+    /// Whether the exclusion applies to `table`.
+    pub(crate) fn applies_to(&self, table: &[u8]) -> bool {
+        self.table
+            .as_ref()
+            .is_none_or(|name| name.as_bytes().eq_ignore_ascii_case(table))
+    }
+}
+
+/// One column of a table, as reported by `PRAGMA table_xinfo`.
+pub(crate) struct TableColumn {
+    pub(crate) name: Vec<u8>,
+    decl_type: Vec<u8>,
+    not_null: bool,
+    default: Option<Vec<u8>>,
+    /// 1-based position in the primary key, or 0 if not part of it
+    pk: i64,
+    /// 0 for a normal column, 1 for a hidden column of a virtual table,
+    /// 2 for a virtual generated column and 3 for a stored generated column
+    hidden: i64,
+    /// Whether the value depends on the cursor reading the table
+    per_cursor: bool,
+}
+
+impl TableColumn {
+    /// Read a row of `pragma_table_xinfo`.
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let bytes = |i: &str| -> rusqlite::Result<Option<Vec<u8>>> {
+            Ok(row.get_ref(i)?.as_bytes_or_null()?.map(<[u8]>::to_vec))
+        };
+        Ok(Self {
+            name: bytes("name")?.unwrap_or_default(),
+            decl_type: bytes("type")?.unwrap_or_default(),
+            not_null: row.get("notnull")?,
+            default: bytes("dflt_value")?,
+            pk: row.get("pk")?,
+            hidden: row.get("hidden")?,
+            per_cursor: false,
+        })
+    }
+
+    /// Whether the column is hashed under `policy`.
+    pub(crate) fn is_hashed(&self, policy: ColumnPolicy) -> bool {
+        match policy {
+            ColumnPolicy::Visible => self.hidden != 1,
+            ColumnPolicy::ExcludeGenerated => self.hidden == 0,
+            ColumnPolicy::IncludeHidden => !self.per_cursor,
+        }
+    }
+}
+
+/// List the columns of table `name` of database `schema` in declared order.
+pub(crate) fn table_columns(
+    conn: &Connection,
+    schema: &str,
+    name: &[u8],
+) -> rusqlite::Result<Vec<TableColumn>> {
+    let mut stmt = conn.prepare("SELECT * FROM pragma_table_xinfo(?1, ?2) ORDER BY cid")?;
+    let mut columns = stmt
+        .query_map(
+            (
+                ToSqlOutput::Borrowed(ValueRef::Text(name)),
+                ToSqlOutput::Borrowed(ValueRef::Text(schema.as_bytes())),
+            ),
+            TableColumn::from_row,
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // Full-text search tables have a hidden column named after the table,
+    // holding the id of the cursor, and FTS5 a `rank` column only defined
+    // for full-text queries.
+    let is_hidden_named = |column: &TableColumn, other: &[u8]| {
+        column.hidden == 1 && column.name.eq_ignore_ascii_case(other)
+    };
+    if columns.iter().any(|column| is_hidden_named(column, name)) {
+        for column in &mut columns {
+            column.per_cursor = is_hidden_named(column, name) || is_hidden_named(column, b"rank");
+        }
+    }
+    Ok(columns)
+}
+
+/// Append `name` to `out` as a quoted identifier, escaping each double-quote
+/// into two double-quotes.
+pub(crate) fn push_identifier(out: &mut Vec<u8>, name: &[u8]) {
+    out.push(b'"');
+    for &byte in name {
+        out.push(byte);
+        if byte == b'"' {
+            out.push(byte);
+        }
+    }
+    out.push(b'"');
+}
+
+/// Quote `name` as an identifier, like [`push_identifier`].
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Describe the structure of table `name` with `columns` as a canonical
+/// `CREATE TABLE` statement. Unlike the SQL text in `sqlite_schema`, it does
+/// not depend on formatting, and omits the expressions of generated columns
+/// as well as table constraints other than the primary key.
+pub(crate) fn structural_sql<'c>(
+    name: &[u8],
+    columns: impl IntoIterator<Item = &'c TableColumn>,
+) -> Vec<u8> {
+    let mut sql = b"CREATE TABLE ".to_vec();
+    push_identifier(&mut sql, name);
+    sql.extend_from_slice(b" (");
+
+    let mut primary_key = Vec::new();
+    for (i, column) in columns.into_iter().enumerate() {
+        if i > 0 {
+            sql.extend_from_slice(b", ");
+        }
+        push_identifier(&mut sql, &column.name);
+        if !column.decl_type.is_empty() {
+            sql.push(b' ');
+            sql.extend_from_slice(&column.decl_type);
+        }
+        if column.not_null {
+            sql.extend_from_slice(b" NOT NULL");
+        }
+        if let Some(default) = &column.default {
+            sql.extend_from_slice(b" DEFAULT ");
+            sql.extend_from_slice(default);
+        }
+        match column.hidden {
+            2 => sql.extend_from_slice(b" GENERATED ALWAYS VIRTUAL"),
+            3 => sql.extend_from_slice(b" GENERATED ALWAYS STORED"),
+            _ => (),
+        }
+        if column.pk > 0 {
+            primary_key.push((column.pk, &column.name));
+        }
+    }
+
+    if !primary_key.is_empty() {
+        primary_key.sort_by_key(|(pk, _)| *pk);
+        sql.extend_from_slice(b", PRIMARY KEY (");
+        for (i, (_, name)) in primary_key.into_iter().enumerate() {
+            if i > 0 {
+                sql.extend_from_slice(b", ");
+            }
+            push_identifier(&mut sql, name);
+        }
+        sql.push(b')');
+    }
+    sql.push(b')');
+    sql
+}
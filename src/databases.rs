@@ -0,0 +1,114 @@
+//! Hashes of all the databases attached to a connection.
+use std::fmt::{self, Debug};
+
+use digest::{Digest, OutputSizeUser};
+use sha1::Sha1;
+
+use crate::DbHash;
+
+/// The hashes of the databases attached to a connection, each under its
+/// name, and a root hash combining them, as produced by
+/// [`HashOptions::hash_databases`](crate::HashOptions::hash_databases).
+///
+/// The databases are ordered by name, so the root hash does not depend on
+/// the order in which they were attached.
+///
+/// # Examples
+/// ```no_run
+/// # use sqlite_dbhash::HashOptions;
+/// # use rusqlite::Connection;
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let conn = Connection::open("app.db")?;
+///     conn.execute("ATTACH DATABASE 'archive.db' AS archive", [])?;
+///     let hashes = HashOptions::new().hash_databases(&conn)?;
+///     for (name, hash) in hashes.iter() {
+///         println!("{name}: {hash}");
+///     }
+///     println!("root: {}", hashes.root());
+///     Ok(())
+/// }
+/// ```
+pub struct DatabaseHashes<D: OutputSizeUser = Sha1> {
+    databases: Vec<(String, DbHash<D>)>,
+    root: DbHash<D>,
+}
+
+impl<D: Digest> DatabaseHashes<D> {
+    /// Combine the hash of each database under its name.
+    pub(crate) fn new(mut databases: Vec<(String, DbHash<D>)>) -> Self {
+        databases.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut hasher = D::new();
+        for (name, hash) in &databases {
+            hasher.update((name.len() as u64).to_be_bytes());
+            hasher.update(name);
+            hasher.update(hash);
+        }
+        Self {
+            databases,
+            root: DbHash::from_output(hasher.finalize()),
+        }
+    }
+}
+
+impl<D: OutputSizeUser> DatabaseHashes<D> {
+    /// The root hash combining the hashes of all databases.
+    ///
+    /// It is the digest of, for each database in order of name, the length
+    /// of the name in bytes as a 64-bit big-endian integer, the UTF-8 bytes
+    /// of the name, and the hash of the database.
+    pub fn root(&self) -> &DbHash<D> {
+        &self.root
+    }
+
+    /// The hash of the database named `name`, either its alias or the
+    /// logical name given to it.
+    pub fn get(&self, name: &str) -> Option<&DbHash<D>> {
+        self.databases
+            .iter()
+            .find(|(database, _)| database == name)
+            .map(|(_, hash)| hash)
+    }
+
+    /// Iterate over the names and hashes of the databases, in order of name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &DbHash<D>)> {
+        self.databases
+            .iter()
+            .map(|(name, hash)| (name.as_str(), hash))
+    }
+
+    /// Number of databases hashed.
+    pub fn len(&self) -> usize {
+        self.databases.len()
+    }
+
+    /// Whether no database was hashed.
+    pub fn is_empty(&self) -> bool {
+        self.databases.is_empty()
+    }
+}
+
+impl<D: OutputSizeUser> Clone for DatabaseHashes<D> {
+    fn clone(&self) -> Self {
+        Self {
+            databases: self.databases.clone(),
+            root: self.root.clone(),
+        }
+    }
+}
+
+impl<D: OutputSizeUser> PartialEq for DatabaseHashes<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.root == other.root && self.databases == other.databases
+    }
+}
+
+impl<D: OutputSizeUser> Eq for DatabaseHashes<D> {}
+
+impl<D: OutputSizeUser> Debug for DatabaseHashes<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatabaseHashes")
+            .field("databases", &self.databases)
+            .field("root", &self.root)
+            .finish()
+    }
+}
@@ -0,0 +1,156 @@
+//! Errors produced while hashing a database.
+use std::{
+    fmt::{self, Display},
+    path::PathBuf,
+};
+
+use rusqlite::ErrorCode;
+
+/// Result type of this crate.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The object being processed when an [`Error`] happened.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Object {
+    /// Listing the tables to hash from `sqlite_schema`.
+    TableList,
+    /// The content of a table. Names that are not valid UTF-8 are
+    /// converted lossily.
+    Table(String),
+    /// The content of a view, see
+    /// [`HashOptions::view_content`](crate::HashOptions::view_content).
+    View(String),
+    /// The entries of `sqlite_schema`.
+    Schema,
+    /// A database setting read with `PRAGMA`, see
+    /// [`HashOptions::settings`](crate::HashOptions::settings).
+    Setting(String),
+    /// A query given by the caller.
+    Query,
+    /// A database file being opened.
+    File(PathBuf),
+    /// An in-memory database image being opened.
+    Image,
+    /// A database attached to the connection, see
+    /// [`HashOptions::schema_name`](crate::HashOptions::schema_name).
+    Database(String),
+    /// Listing the databases attached to the connection, see
+    /// [`HashOptions::hash_databases`](crate::HashOptions::hash_databases).
+    DatabaseList,
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TableList => write!(f, "table list"),
+            Self::Table(name) => write!(f, "table {name:?}"),
+            Self::View(name) => write!(f, "view {name:?}"),
+            Self::Schema => write!(f, "schema"),
+            Self::Setting(name) => write!(f, "setting {name:?}"),
+            Self::Query => write!(f, "query"),
+            Self::File(path) => write!(f, "file {:?}", path.display()),
+            Self::Image => write!(f, "database image"),
+            Self::Database(name) => write!(f, "database {name:?}"),
+            Self::DatabaseList => write!(f, "database list"),
+        }
+    }
+}
+
+/// An error produced while hashing a database.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// SQLite reported an error.
+    Sqlite {
+        /// The object being processed
+        object: Object,
+        /// The error from SQLite
+        source: rusqlite::Error,
+    },
+    /// The schema of the database changed while it was being hashed.
+    SchemaChanged {
+        /// The object being processed
+        object: Object,
+    },
+    /// The database file, or the table, attached database or setting to hash,
+    /// does not exist.
+    NotFound {
+        /// The missing object
+        object: Object,
+    },
+    /// Two databases would be hashed under the same name by
+    /// [`HashOptions::hash_databases`](crate::HashOptions::hash_databases),
+    /// through [`HashOptions::logical_name`](crate::HashOptions::logical_name).
+    DuplicateName {
+        /// The object named twice
+        object: Object,
+    },
+    /// The file is not a SQLite database.
+    NotADatabase {
+        /// The object being processed
+        object: Object,
+    },
+    /// Hashing was cancelled, either by the progress callback set with
+    /// [`HashOptions::on_progress`](crate::HashOptions::on_progress) or by
+    /// interrupting the connection.
+    Cancelled {
+        /// The object being processed
+        object: Object,
+    },
+}
+
+impl Error {
+    /// The object being processed when the error happened.
+    pub fn object(&self) -> &Object {
+        match self {
+            Self::Sqlite { object, .. }
+            | Self::SchemaChanged { object }
+            | Self::NotFound { object }
+            | Self::DuplicateName { object }
+            | Self::NotADatabase { object }
+            | Self::Cancelled { object } => object,
+        }
+    }
+
+    /// Create a mapping from SQLite errors happening while processing `object`.
+    pub(crate) fn sqlite(object: &Object) -> impl FnOnce(rusqlite::Error) -> Self {
+        move |source| {
+            let object = object.clone();
+            match source.sqlite_error_code() {
+                Some(ErrorCode::OperationInterrupted) => Self::Cancelled { object },
+                Some(ErrorCode::SchemaChanged) => Self::SchemaChanged { object },
+                Some(ErrorCode::NotADatabase) => Self::NotADatabase { object },
+                _ => Self::Sqlite { object, source },
+            }
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sqlite { object, source } => {
+                write!(f, "sqlite error while hashing {object}: {source}")
+            }
+            Self::SchemaChanged { object } => {
+                write!(f, "schema changed while hashing {object}")
+            }
+            Self::NotFound { object } => write!(f, "{object} does not exist"),
+            Self::DuplicateName { object } => write!(f, "{object} is named more than once"),
+            Self::NotADatabase { object } => {
+                write!(f, "not a database while hashing {object}")
+            }
+            Self::Cancelled { object } => write!(f, "cancelled while hashing {object}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Sqlite { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Extension trait to hash a database directly through a connection.
+use rusqlite::{Connection, Savepoint, Transaction};
+
+use crate::{DbHash, HashOptions, Result, Selection};
+
+/// Extension methods to hash the database behind a [`Connection`],
+/// a [`Transaction`] or a [`Savepoint`].
+///
+/// Hashing through a transaction or a savepoint includes its uncommitted
+/// changes.
+///
+/// # Examples
+/// ```no_run
+/// # use sqlite_dbhash::ConnectionExt;
+/// # use rusqlite::Connection;
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut conn = Connection::open("my_db.db")?;
+///     let before = conn.dbhash()?;
+///
+///     let tx = conn.transaction()?;
+///     tx.execute("DELETE FROM t WHERE id = 1", [])?;
+///     println!("{before} -> {}", tx.dbhash()?);
+///     tx.rollback()?;
+///     Ok(())
+/// }
+/// ```
+pub trait ConnectionExt: private::Sealed {
+    /// Compute the SHA1 hash of the whole database, schema and content.
+    /// Equivalent to `dbhash`.
+    fn dbhash(&self) -> Result<DbHash> {
+        HashOptions::new().hash(self.connection())
+    }
+
+    /// Compute the SHA1 hash of the schema of the database.
+    /// Equivalent to `dbhash --schema-only`.
+    fn schema_hash(&self) -> Result<DbHash> {
+        HashOptions::new()
+            .selection(Selection::SchemaOnly)
+            .hash(self.connection())
+    }
+
+    /// Compute the SHA1 hash of the content of the database.
+    /// Equivalent to `dbhash --without-schema`.
+    fn content_hash(&self) -> Result<DbHash> {
+        HashOptions::new()
+            .selection(Selection::ContentOnly)
+            .hash(self.connection())
+    }
+
+    /// Compute the SHA1 hash of the schema and content of table `name`,
+    /// see [`HashOptions::table_name`].
+    fn table_hash(&self, name: &str) -> Result<DbHash> {
+        HashOptions::new().table_name(name).hash(self.connection())
+    }
+}
+
+impl ConnectionExt for Connection {}
+
+impl ConnectionExt for Transaction<'_> {}
+
+impl ConnectionExt for Savepoint<'_> {}
+
+mod private {
+    use rusqlite::{Connection, Savepoint, Transaction};
+
+    /// Prevent implementing [`ConnectionExt`](super::ConnectionExt) outside
+    /// of this crate, and give access to the underlying connection.
+    pub trait Sealed {
+        fn connection(&self) -> &Connection;
+    }
+
+    impl Sealed for Connection {
+        fn connection(&self) -> &Connection {
+            self
+        }
+    }
+
+    impl Sealed for Transaction<'_> {
+        fn connection(&self) -> &Connection {
+            self
+        }
+    }
+
+    impl Sealed for Savepoint<'_> {
+        fn connection(&self) -> &Connection {
+            self
+        }
+    }
+}
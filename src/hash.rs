@@ -0,0 +1,279 @@
+//! The hash value produced by the library.
+use std::{
+    error::Error,
+    fmt::{self, Debug, Display},
+    str::FromStr,
+};
+
+use digest::{Output, OutputSizeUser};
+use sha1::Sha1;
+use subtle::ConstantTimeEq;
+
+/// The hash of a database, as produced by [`dbhash`](crate::dbhash)
+/// and [`HashOptions::hash`](crate::HashOptions::hash).
+///
+/// It is displayed as lowercase hex, exactly like the hash printed by the
+/// original dbhash utility program, and can be parsed back from such
+/// a string. Comparison between two hashes runs in constant time.
+///
+/// The type parameter is the digest algorithm that produced the hash,
+/// which defaults to SHA1 as used by the original program. See
+/// [`HashOptions::hash_with`](crate::HashOptions::hash_with) for other algorithms.
+///
+/// # Examples
+/// ```
+/// # use sqlite_dbhash::DbHash;
+/// let hash: DbHash = "da39a3ee5e6b4b0d3255bfef95601890afd80709".parse().unwrap();
+/// assert_eq!(hash.to_string(), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+/// assert_eq!(hash.to_base64(), "2jmj7l5rSw0yVb/vlWAYkK/YBwk=");
+/// ```
+pub struct DbHash<D: OutputSizeUser = Sha1>(Output<D>);
+
+impl DbHash {
+    /// Length of a SHA1 hash in bytes.
+    pub const LEN: usize = 20;
+
+    /// Wrap the raw bytes of a SHA1 hash.
+    pub fn from_bytes(bytes: [u8; 20]) -> Self {
+        Self(bytes.into())
+    }
+
+    /// Consume the hash and return its raw bytes.
+    pub fn into_bytes(self) -> [u8; 20] {
+        self.0.into()
+    }
+}
+
+impl<D: OutputSizeUser> DbHash<D> {
+    /// Wrap the output of digest `D`.
+    pub fn from_output(output: Output<D>) -> Self {
+        Self(output)
+    }
+
+    /// Consume the hash and return the output of digest `D`.
+    pub fn into_output(self) -> Output<D> {
+        self.0
+    }
+
+    /// The raw bytes of the hash.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Encode the hash as padded standard base64.
+    pub fn to_base64(&self) -> String {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        let mut encoded = String::with_capacity(self.0.len().div_ceil(3) * 4);
+        for chunk in self.0.chunks(3) {
+            let group = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |acc, (i, &byte)| acc | (byte as u32) << (16 - 8 * i));
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    let index = (group >> (18 - 6 * i)) & 0x3f;
+                    encoded.push(ALPHABET[index as usize] as char);
+                } else {
+                    encoded.push('=');
+                }
+            }
+        }
+        encoded
+    }
+}
+
+impl From<[u8; 20]> for DbHash {
+    fn from(bytes: [u8; 20]) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+
+impl From<DbHash> for [u8; 20] {
+    fn from(hash: DbHash) -> Self {
+        hash.into_bytes()
+    }
+}
+
+impl<D: OutputSizeUser> AsRef<[u8]> for DbHash<D> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<D: OutputSizeUser> Clone for DbHash<D> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<D: OutputSizeUser> Copy for DbHash<D> where Output<D>: Copy {}
+
+impl<D: OutputSizeUser> PartialEq for DbHash<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ct_eq(&other.0).into()
+    }
+}
+
+impl<D: OutputSizeUser> Eq for DbHash<D> {}
+
+impl<D: OutputSizeUser> std::hash::Hash for DbHash<D> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<D: OutputSizeUser> Display for DbHash<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(self, f)
+    }
+}
+
+impl<D: OutputSizeUser> Debug for DbHash<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DbHash({self:x})")
+    }
+}
+
+impl<D: OutputSizeUser> fmt::LowerHex for DbHash<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+impl<D: OutputSizeUser> fmt::UpperHex for DbHash<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02X}"))
+    }
+}
+
+impl<D: OutputSizeUser> FromStr for DbHash<D> {
+    type Err = ParseDbHashError;
+
+    /// Parse a hash from its hex representation, in either case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((index, character)) = s.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+            return Err(ParseDbHashError::InvalidCharacter { character, index });
+        }
+        let expected = 2 * D::output_size();
+        if s.len() != expected {
+            return Err(ParseDbHashError::InvalidLength {
+                expected,
+                found: s.len(),
+            });
+        }
+
+        let mut bytes = Output::<D>::default();
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            // All characters are ASCII hex digits at this point
+            *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16)
+                .expect("hex digits should be parsable");
+        }
+
+        Ok(Self(bytes))
+    }
+}
+
+/// Error returned when parsing a [`DbHash`] from a string fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseDbHashError {
+    /// The string does not have the number of hex digits of the digest.
+    InvalidLength {
+        /// Number of hex digits of the digest
+        expected: usize,
+        /// Length of the string
+        found: usize,
+    },
+    /// The string contains a character that is not a hex digit.
+    InvalidCharacter {
+        /// The offending character
+        character: char,
+        /// Byte offset of the character in the string
+        index: usize,
+    },
+}
+
+impl Display for ParseDbHashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength { expected, found } => {
+                write!(f, "expected {expected} hex digits, found {found}")
+            }
+            Self::InvalidCharacter { character, index } => {
+                write!(f, "invalid hex digit {character:?} at index {index}")
+            }
+        }
+    }
+}
+
+impl Error for ParseDbHashError {}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use std::{fmt, marker::PhantomData};
+
+    use digest::{Output, OutputSizeUser};
+    use serde::{
+        Deserialize, Deserializer, Serialize, Serializer,
+        de::{self, Visitor},
+    };
+
+    use super::DbHash;
+
+    /// Hashes are serialized as hex strings for human-readable formats
+    /// and as raw bytes otherwise.
+    impl<D: OutputSizeUser> Serialize for DbHash<D> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                serializer.collect_str(self)
+            } else {
+                serializer.serialize_bytes(&self.0)
+            }
+        }
+    }
+
+    impl<'de, D: OutputSizeUser> Deserialize<'de> for DbHash<D> {
+        fn deserialize<De: Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+            struct DbHashVisitor<D>(PhantomData<D>);
+
+            impl<'de, D: OutputSizeUser> Visitor<'de> for DbHashVisitor<D> {
+                type Value = DbHash<D>;
+
+                fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "a hex string or {} bytes", D::output_size())
+                }
+
+                fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                    v.parse().map_err(E::custom)
+                }
+
+                fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                    if v.len() != D::output_size() {
+                        return Err(E::invalid_length(v.len(), &self));
+                    }
+                    Ok(DbHash(Output::<D>::clone_from_slice(v)))
+                }
+
+                fn visit_seq<A: de::SeqAccess<'de>>(
+                    self,
+                    mut seq: A,
+                ) -> Result<Self::Value, A::Error> {
+                    let mut bytes = Output::<D>::default();
+                    for (i, byte) in bytes.iter_mut().enumerate() {
+                        *byte = seq
+                            .next_element()?
+                            .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+                    }
+                    Ok(DbHash(bytes))
+                }
+            }
+
+            if deserializer.is_human_readable() {
+                deserializer.deserialize_str(DbHashVisitor(PhantomData))
+            } else {
+                deserializer.deserialize_bytes(DbHashVisitor(PhantomData))
+            }
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Opening database files and images safely for hashing.
+use std::{fmt::Write, path::Path, time::Duration};
+
+use digest::Digest;
+use rusqlite::{Connection, OpenFlags};
+use sha1::Sha1;
+
+use crate::{DbHash, Error, HashOptions, Object, Result, traverse::schema_version};
+
+/// Options for opening a database file to hash.
+///
+/// The file is always opened read-only and is never created, so hashing
+/// does not write to the database or take write locks. Unless the file is
+/// opened with [`OpenOptions::immutable`], SQLite may still create the `-wal`
+/// and `-shm` files next to a database in WAL mode.
+///
+/// # Examples
+/// ```no_run
+/// # use std::time::Duration;
+/// # use sqlite_dbhash::{dbhash_path, OpenOptions};
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let opts = OpenOptions::new()
+///         .immutable(true)
+///         .busy_timeout(Duration::from_secs(5));
+///     println!("{}", dbhash_path("my_db.db", &opts)?);
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OpenOptions {
+    immutable: bool,
+    busy_timeout: Option<Duration>,
+}
+
+impl OpenOptions {
+    /// Create options that open the file read-only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the file with the `immutable=1` URI parameter, which tells SQLite
+    /// that the file cannot change. SQLite then takes no locks and creates no
+    /// `-wal` or `-shm` files, but the hash is undefined if the file is
+    /// modified while it is being hashed.
+    pub fn immutable(mut self, immutable: bool) -> Self {
+        self.immutable = immutable;
+        self
+    }
+
+    /// Wait up to `timeout` for locks held by other connections to be released.
+    pub fn busy_timeout(mut self, timeout: Duration) -> Self {
+        self.busy_timeout = Some(timeout);
+        self
+    }
+
+    /// Open the database file at `path` for hashing.
+    ///
+    /// Fails with [`Error::NotFound`] if the file does not exist, and with
+    /// [`Error::NotADatabase`] if the file is not a SQLite database.
+    pub fn open(&self, path: impl AsRef<Path>) -> Result<Connection> {
+        let path = path.as_ref();
+        let object = Object::File(path.to_owned());
+        if !path.exists() {
+            return Err(Error::NotFound { object });
+        }
+
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let conn = if self.immutable {
+            Connection::open_with_flags(file_uri(path, "immutable=1"), flags)
+        } else {
+            Connection::open_with_flags(path, flags)
+        }
+        .map_err(Error::sqlite(&object))?;
+
+        if let Some(timeout) = self.busy_timeout {
+            conn.busy_timeout(timeout).map_err(Error::sqlite(&object))?;
+        }
+        // SQLite only reads the file lazily, so read the header now to
+        // detect files that are not databases.
+        schema_version(&conn, "main").map_err(Error::sqlite(&object))?;
+
+        Ok(conn)
+    }
+}
+
+impl HashOptions {
+    /// Compute the SHA1 hash of the database file at `path`, opened with `open`.
+    pub fn hash_path(&self, path: impl AsRef<Path>, open: &OpenOptions) -> Result<DbHash> {
+        self.hash_path_with::<Sha1>(path, open)
+    }
+
+    /// Compute the hash of the database file at `path` like
+    /// [`HashOptions::hash_path`], but with digest algorithm `D` instead of SHA1.
+    pub fn hash_path_with<D: Digest>(
+        &self,
+        path: impl AsRef<Path>,
+        open: &OpenOptions,
+    ) -> Result<DbHash<D>> {
+        let conn = open.open(path)?;
+        self.hash_with(&conn)
+    }
+}
+
+/// Compute the SHA1 hash of the whole database file at `path`, opened with `opts`.
+/// Equivalent to `dbhash PATH`.
+///
+/// See [`OpenOptions`] for an example.
+pub fn dbhash_path(path: impl AsRef<Path>, opts: &OpenOptions) -> Result<DbHash> {
+    HashOptions::new().hash_path(path, opts)
+}
+
+/// Open an in-memory read-only database from a copy of `image`.
+#[cfg(feature = "serialize")]
+fn open_image(image: &[u8]) -> Result<Connection> {
+    let object = Object::Image;
+    let mut conn = Connection::open_in_memory().map_err(Error::sqlite(&object))?;
+    // SQLite cannot deserialize an empty image, which is an empty database anyway
+    if !image.is_empty() {
+        // SQLite refuses images of WAL databases, so copy the header and
+        // mark the file format versions at bytes 18 and 19 as legacy
+        let (header, body) = image.split_at(image.len().min(20));
+        let mut header = header.to_vec();
+        for version in header.iter_mut().skip(18) {
+            if *version == 2 {
+                *version = 1;
+            }
+        }
+        conn.deserialize_read_exact(
+            rusqlite::MAIN_DB,
+            std::io::Read::chain(header.as_slice(), body),
+            image.len(),
+            true,
+        )
+        .map_err(Error::sqlite(&object))?;
+    }
+    // Make sure the image is a database
+    schema_version(&conn, "main").map_err(Error::sqlite(&object))?;
+
+    Ok(conn)
+}
+
+#[cfg(feature = "serialize")]
+impl HashOptions {
+    /// Compute the SHA1 hash of the database image `image`, such as the
+    /// content of a database file, without writing it to disk.
+    ///
+    /// The image is copied into an in-memory read-only database. The image of
+    /// a database in WAL mode is accepted, but only holds the content already
+    /// checkpointed into the file. Fails with [`Error::NotADatabase`] if the
+    /// image is not a SQLite database.
+    pub fn hash_bytes(&self, image: &[u8]) -> Result<DbHash> {
+        self.hash_bytes_with::<Sha1>(image)
+    }
+
+    /// Compute the hash of the database image `image` like
+    /// [`HashOptions::hash_bytes`], but with digest algorithm `D` instead of SHA1.
+    pub fn hash_bytes_with<D: Digest>(&self, image: &[u8]) -> Result<DbHash<D>> {
+        let conn = open_image(image)?;
+        self.hash_with(&conn)
+    }
+}
+
+/// Compute the SHA1 hash of the whole database image `image`, such as the
+/// content of a database file. Equivalent to `dbhash` on a file with the
+/// same content.
+///
+/// # Examples
+/// ```no_run
+/// # use sqlite_dbhash::dbhash_bytes;
+/// # fn download_snapshot() -> Vec<u8> { Vec::new() }
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let image: Vec<u8> = download_snapshot();
+///     println!("{}", dbhash_bytes(&image)?);
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "serialize")]
+pub fn dbhash_bytes(image: &[u8]) -> Result<DbHash> {
+    HashOptions::new().hash_bytes(image)
+}
+
+/// Build a SQLite URI for the file at `path` with `query`.
+fn file_uri(path: &Path, query: &str) -> String {
+    let mut uri = String::from("file:");
+    let path = path.as_os_str().as_encoded_bytes();
+    // Absolute Windows paths with a drive letter need an empty authority
+    if cfg!(windows) && path.get(1) == Some(&b':') {
+        uri.push_str("///");
+    }
+    for &byte in path {
+        match byte {
+            b'\\' if cfg!(windows) => uri.push('/'),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => write!(uri, "%{byte:02X}").expect("writing to a string cannot fail"),
+        }
+    }
+    uri.push('?');
+    uri.push_str(query);
+    uri
+}
@@ -0,0 +1,723 @@
+//! Builder for configuring how a database is hashed.
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Debug},
+    ops::ControlFlow,
+    slice,
+    sync::Arc,
+};
+
+use digest::Digest;
+use rusqlite::{Connection, types::Value};
+use sha1::Sha1;
+#[cfg(feature = "tracing")]
+use tracing::{Level, span};
+
+use crate::{
+    ColumnPolicy, DatabaseHashes, DbHash, DigestSink, Error, HashSink, Object, Result, Selection,
+    TableMatcher,
+    columns::{ColumnExclusion, TableColumn},
+    rows::{RowFilter, TenantPolicy, TenantScope},
+    traverse::{Traversal, check_tables, resolve_schema},
+};
+
+/// Options controlling what part of a database is hashed and how.
+///
+/// The default options hash the whole database, schema and content,
+/// exactly like running the original dbhash utility program without
+/// any arguments. Each knob is set through a builder method, and the
+/// hash is computed with [`HashOptions::hash`].
+///
+/// Names of tables, columns and databases given to the options are matched
+/// ignoring ASCII case, the same way SQLite resolves identifiers.
+///
+/// # Examples
+/// ```no_run
+/// # use sqlite_dbhash::{HashOptions, Selection};
+/// # use rusqlite::Connection;
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let conn = Connection::open("my_db.db")?;
+///     // Equivalent to `dbhash --like "prefix%" --schema-only my_db.db`
+///     let hash = HashOptions::new()
+///         .table_pattern("prefix%")
+///         .selection(Selection::SchemaOnly)
+///         .hash(&conn)?;
+///     println!("{hash}");
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HashOptions {
+    pub(crate) includes: Vec<TableMatcher>,
+    pub(crate) excludes: Vec<TableMatcher>,
+    pub(crate) foreign_key_roots: Vec<String>,
+    pub(crate) selection: Selection,
+    pub(crate) column_exclusions: Vec<ColumnExclusion>,
+    pub(crate) column_policy: ColumnPolicy,
+    pub(crate) structural_schema: bool,
+    pub(crate) settings: BTreeSet<String>,
+    pub(crate) view_content: bool,
+    pub(crate) virtual_table_content: bool,
+    pub(crate) sequence_content: bool,
+    pub(crate) stat_content: bool,
+    pub(crate) row_filters: Vec<RowFilter>,
+    pub(crate) tenant: Option<TenantScope>,
+    pub(crate) schema_name: Option<String>,
+    pub(crate) logical_names: Vec<(String, String)>,
+    pub(crate) progress: Option<ProgressCallback>,
+}
+
+impl HashOptions {
+    /// The settings stored in the database file itself, for
+    /// [`HashOptions::settings`]. Unlike settings such as `foreign_keys`,
+    /// they do not depend on the connection reading the database.
+    pub const PERSISTENT_SETTINGS: [&str; 5] = [
+        "application_id",
+        "auto_vacuum",
+        "encoding",
+        "page_size",
+        "user_version",
+    ];
+
+    /// Create options that hash the whole database.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only hash the tables whose name is LIKE `pattern`. This is
+    /// equivalent to `--like PATTERN` argument in the original dbhash
+    /// utility program.
+    ///
+    /// This is a shorthand for [`HashOptions::include`] with
+    /// [`TableMatcher::Like`].
+    pub fn table_pattern(self, pattern: impl Into<String>) -> Self {
+        self.table_pattern_bytes(pattern.into())
+    }
+
+    /// Only hash the tables whose name is LIKE `pattern`, given as raw bytes
+    /// that are not necessarily valid UTF-8.
+    ///
+    /// The bytes are matched exactly as the original dbhash utility program
+    /// matches its `--like PATTERN` argument, so passing the raw bytes of the
+    /// argument reproduces its hash bit-for-bit.
+    pub fn table_pattern_bytes(self, pattern: impl Into<Vec<u8>>) -> Self {
+        self.include(TableMatcher::Like(pattern.into()))
+    }
+
+    /// Only hash the table named `name`, without any wildcard.
+    ///
+    /// This is a shorthand for [`HashOptions::include`] with
+    /// [`TableMatcher::Exact`].
+    pub fn table_name(self, name: impl Into<String>) -> Self {
+        self.include(TableMatcher::Exact(name.into()))
+    }
+
+    /// Only hash the tables named in `names`, see [`HashOptions::table_name`].
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use sqlite_dbhash::HashOptions;
+    /// # use rusqlite::Connection;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let conn = Connection::open("my_db.db")?;
+    ///     // Does not hash `userXroles`, unlike `table_pattern("user_roles")`
+    ///     let hash = HashOptions::new()
+    ///         .table_names(["users", "user_roles"])
+    ///         .hash(&conn)?;
+    ///     println!("{hash}");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn table_names<I>(self, names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        names.into_iter().fold(self, Self::table_name)
+    }
+
+    /// Only hash the tables matching `matcher`.
+    ///
+    /// Table rules are combined: once any is given, a table is hashed if it
+    /// matches any of them.
+    pub fn include(mut self, matcher: TableMatcher) -> Self {
+        self.includes.push(matcher);
+        self
+    }
+
+    /// Do not hash the tables matching `matcher`, from both the content and
+    /// the schema, even if they match an include rule.
+    pub fn exclude(mut self, matcher: TableMatcher) -> Self {
+        self.excludes.push(matcher);
+        self
+    }
+
+    /// Only hash table `root` and the tables connected to it through foreign
+    /// keys, in either direction: the tables it references, the tables
+    /// referencing it, and so on transitively. Their indexes and triggers are
+    /// part of the schema hash as usual.
+    ///
+    /// The closure is found by walking `PRAGMA foreign_key_list` of every
+    /// table when hashing starts. It is combined with the other include
+    /// rules, and hashing fails with [`Error::NotFound`](crate::Error::NotFound)
+    /// if there is no table named `root`.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use sqlite_dbhash::HashOptions;
+    /// # use rusqlite::Connection;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let conn = Connection::open("my_db.db")?;
+    ///     // Hashes `customers`, their `orders`, the `order_items`, ...
+    ///     let hash = HashOptions::new()
+    ///         .foreign_key_closure("customers")
+    ///         .hash(&conn)?;
+    ///     println!("{hash}");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn foreign_key_closure(mut self, root: impl Into<String>) -> Self {
+        self.foreign_key_roots.push(root.into());
+        self
+    }
+
+    /// Do not hash the tables whose name is LIKE `pattern`.
+    ///
+    /// Exclusions apply after the table patterns and names: a table matching
+    /// any exclusion is skipped, from both the content and the schema.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use sqlite_dbhash::HashOptions;
+    /// # use rusqlite::Connection;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let conn = Connection::open("my_db.db")?;
+    ///     let hash = HashOptions::new()
+    ///         .exclude_pattern("audit_%")
+    ///         .exclude_pattern("cache_%")
+    ///         .hash(&conn)?;
+    ///     println!("{hash}");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn exclude_pattern(self, pattern: impl Into<String>) -> Self {
+        self.exclude_pattern_bytes(pattern.into())
+    }
+
+    /// Do not hash the tables whose name is LIKE `pattern`, given as raw
+    /// bytes that are not necessarily valid UTF-8.
+    pub fn exclude_pattern_bytes(self, pattern: impl Into<Vec<u8>>) -> Self {
+        self.exclude(TableMatcher::Like(pattern.into()))
+    }
+
+    /// Do not hash the tables whose name matches the GLOB `pattern`.
+    /// Unlike LIKE, GLOB is case-sensitive and uses `*` and `?` wildcards.
+    pub fn exclude_glob(self, pattern: impl Into<String>) -> Self {
+        self.exclude(TableMatcher::Glob(pattern.into()))
+    }
+
+    /// Do not hash the table named `name`.
+    pub fn exclude_name(self, name: impl Into<String>) -> Self {
+        self.exclude(TableMatcher::Exact(name.into()))
+    }
+
+    /// Choose whether to hash schema, content, or both.
+    pub fn selection(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Leave `column` of `table` out of the content hash.
+    ///
+    /// Instead of `SELECT *`, the content of the table is read by selecting
+    /// the remaining columns in declared order. If no column remains, the
+    /// table contributes nothing to the content hash, as if it were empty.
+    ///
+    /// The column is still part of the `CREATE TABLE` statement in the
+    /// schema, unless [`HashOptions::structural_schema`] is enabled.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use sqlite_dbhash::HashOptions;
+    /// # use rusqlite::Connection;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let conn = Connection::open("my_db.db")?;
+    ///     // Replicas agree regardless of when they last saw each user
+    ///     let hash = HashOptions::new()
+    ///         .exclude_column("users", "last_seen")
+    ///         .exclude_column_everywhere("updated_at")
+    ///         .structural_schema(true)
+    ///         .hash(&conn)?;
+    ///     println!("{hash}");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn exclude_column(mut self, table: impl Into<String>, column: impl Into<String>) -> Self {
+        self.column_exclusions.push(ColumnExclusion {
+            table: Some(table.into()),
+            column: column.into(),
+        });
+        self
+    }
+
+    /// Leave `column` out of the content hash of every table that has it,
+    /// see [`HashOptions::exclude_column`].
+    pub fn exclude_column_everywhere(mut self, column: impl Into<String>) -> Self {
+        self.column_exclusions.push(ColumnExclusion {
+            table: None,
+            column: column.into(),
+        });
+        self
+    }
+
+    /// Only hash the rows of `table` satisfying `predicate`, an SQL
+    /// expression used as the `WHERE` clause when reading the table. A later
+    /// filter on the same table replaces the earlier one. Hashing the content
+    /// fails with [`Error::NotFound`](crate::Error::NotFound) if the database
+    /// has no table or view named `table`.
+    ///
+    /// The predicate refers to `params` through `?` placeholders, bound in
+    /// order, so that values never need to be interpolated into the SQL.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use sqlite_dbhash::HashOptions;
+    /// # use rusqlite::Connection;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let conn = Connection::open("my_db.db")?;
+    ///     let hash = HashOptions::new()
+    ///         .row_filter("orders", "created_at >= ?", ["2026-01-01".to_owned()])
+    ///         .hash(&conn)?;
+    ///     println!("{hash}");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn row_filter<P>(
+        mut self,
+        table: impl Into<String>,
+        predicate: impl Into<String>,
+        params: P,
+    ) -> Self
+    where
+        P: IntoIterator,
+        P::Item: Into<Value>,
+    {
+        let filter = RowFilter {
+            table: table.into(),
+            predicate: predicate.into(),
+            params: params.into_iter().map(Into::into).collect(),
+        };
+        self.row_filters
+            .retain(|f| !f.table.eq_ignore_ascii_case(&filter.table));
+        self.row_filters.push(filter);
+        self
+    }
+
+    /// Only hash the slice of one tenant: the rows of each table having
+    /// `column` are restricted to those where `column = value`, and the
+    /// tables without it are handled according to `policy`. The restriction
+    /// combines with any [`HashOptions::row_filter`] on the same table.
+    ///
+    /// The schema is hashed as usual, regardless of the tenant.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use sqlite_dbhash::{HashOptions, Selection, TenantPolicy};
+    /// # use rusqlite::Connection;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let conn = Connection::open("my_db.db")?;
+    ///     let hash = HashOptions::new()
+    ///         .tenant("tenant_id", 42, TenantPolicy::SkipTable)
+    ///         .selection(Selection::ContentOnly)
+    ///         .hash(&conn)?;
+    ///     println!("{hash}");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn tenant(
+        mut self,
+        column: impl Into<String>,
+        value: impl Into<Value>,
+        policy: TenantPolicy,
+    ) -> Self {
+        self.tenant = Some(TenantScope {
+            column: column.into(),
+            value: value.into(),
+            policy,
+        });
+        self
+    }
+
+    /// Also hash the result set of each view selected by the options as part
+    /// of the content, to verify what readers of the views actually see.
+    ///
+    /// Views are hashed after all tables, ordered by name like tables, with
+    /// their rows sorted by all of their columns so that the order does not
+    /// depend on the query plan. Column exclusions, row filters and the
+    /// tenant apply to views as they do to tables.
+    pub fn view_content(mut self, enabled: bool) -> Self {
+        self.view_content = enabled;
+        self
+    }
+
+    /// Hash the content of virtual tables, such as FTS5 or R-Tree indexes,
+    /// through their public interface with `SELECT *`, and skip their shadow
+    /// tables, which store the same content in an internal layout. Two
+    /// virtual tables with the same logical content then hash equal,
+    /// regardless of how their shadow tables happen to be organized.
+    ///
+    /// Virtual tables are hashed in the same order as other tables, with
+    /// their rows sorted by all of their columns. The module of each virtual
+    /// table must be available on the connection. Shadow tables are found
+    /// through `PRAGMA table_list`, which requires SQLite 3.37.0 or later.
+    pub fn virtual_table_content(mut self, enabled: bool) -> Self {
+        self.virtual_table_content = enabled;
+        self
+    }
+
+    /// Choose whether generated and hidden columns are hashed, see
+    /// [`ColumnPolicy`]. Unless the policy is [`ColumnPolicy::Visible`], the
+    /// content of each table is read by selecting the chosen columns in
+    /// declared order, as listed by `PRAGMA table_xinfo`.
+    pub fn column_policy(mut self, policy: ColumnPolicy) -> Self {
+        self.column_policy = policy;
+        self
+    }
+
+    /// Also hash the content of `sqlite_sequence`, which holds the last
+    /// value handed out for each `AUTOINCREMENT` table, so that replicas
+    /// that would assign different new rowids do not hash equal.
+    ///
+    /// It is hashed after all other tables and views, and before the
+    /// statistics tables enabled by [`HashOptions::stat_content`]. Like other
+    /// tables, it is subject to the table selection.
+    pub fn sequence_content(mut self, enabled: bool) -> Self {
+        self.sequence_content = enabled;
+        self
+    }
+
+    /// Also hash the content of the statistics tables used by the query
+    /// planner, such as `sqlite_stat1` and `sqlite_stat4`, which are
+    /// written by `ANALYZE`.
+    ///
+    /// They are hashed last, in order of name. Like other tables, they are
+    /// subject to the table selection.
+    pub fn stat_content(mut self, enabled: bool) -> Self {
+        self.stat_content = enabled;
+        self
+    }
+
+    /// Hash the definition of ordinary tables by their structure instead of
+    /// the SQL text they were created with. Each table is described by its
+    /// columns in declared order, with their declared type, `NOT NULL`,
+    /// default value and primary key, leaving out the columns excluded from
+    /// the content.
+    ///
+    /// Such a hash does not depend on the formatting of the `CREATE TABLE`
+    /// statement, but does not notice changes to anything else either:
+    /// generated columns are only marked `VIRTUAL` or `STORED`, without
+    /// their expression, and foreign keys, `UNIQUE` and `CHECK` constraints
+    /// and collations are left out. Combined with
+    /// [`ColumnPolicy::ExcludeGenerated`], a changed generation expression
+    /// goes unnoticed entirely. Other kinds of schema objects are hashed as
+    /// usual.
+    pub fn structural_schema(mut self, structural: bool) -> Self {
+        self.structural_schema = structural;
+        self
+    }
+
+    /// Also hash the value of the database setting `name`, such as
+    /// `user_version`, as part of the schema. See [`HashOptions::settings`].
+    pub fn setting(mut self, name: impl Into<String>) -> Self {
+        self.settings.insert(name.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Also hash the values of the database settings `names` as part of the
+    /// schema, so that, for example, databases migrated to different
+    /// versions do not hash equal even if their schemas are the same.
+    ///
+    /// Each setting is read through the table-valued function `pragma_name`,
+    /// and the rows it returns are hashed with its name after the entries of
+    /// the schema, in order of name, whenever any part of the schema is
+    /// selected. SQLite only provides such a function for pragmas returning
+    /// results without side effects, so hashing never changes the database:
+    /// it fails with [`Error::NotFound`] for any other pragma, such as
+    /// `incremental_vacuum`, as well as for `optimize`, `integrity_check` and
+    /// `quick_check`, which do work rather than read a setting.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use sqlite_dbhash::HashOptions;
+    /// # use rusqlite::Connection;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let conn = Connection::open("my_db.db")?;
+    ///     let hash = HashOptions::new()
+    ///         .settings(HashOptions::PERSISTENT_SETTINGS)
+    ///         .setting("foreign_keys")
+    ///         .hash(&conn)?;
+    ///     println!("{hash}");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn settings<I>(self, names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        names.into_iter().fold(self, Self::setting)
+    }
+
+    /// Hash the database attached to the connection as `name` instead of
+    /// `main`, such as `temp` or the alias given to `ATTACH DATABASE`.
+    ///
+    /// Both the schema and the tables are read from that database, so the
+    /// hash is the same as the hash of the database opened on its own.
+    /// Hashing fails with [`Error::NotFound`](crate::Error::NotFound) if no
+    /// database is attached by that name.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use sqlite_dbhash::HashOptions;
+    /// # use rusqlite::Connection;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let conn = Connection::open("my_db.db")?;
+    ///     conn.execute("ATTACH DATABASE 'other.db' AS other", [])?;
+    ///     let hash = HashOptions::new().schema_name("other").hash(&conn)?;
+    ///     println!("{hash}");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn schema_name(mut self, name: impl Into<String>) -> Self {
+        self.schema_name = Some(name.into());
+        self
+    }
+
+    /// Name the database attached as `schema` by `logical` in the result of
+    /// [`HashOptions::hash_databases`], so that the root hash does not
+    /// depend on the alias it happens to be attached under. A later call for
+    /// the same database replaces an earlier one.
+    pub fn logical_name(mut self, schema: impl Into<String>, logical: impl Into<String>) -> Self {
+        let schema = schema.into();
+        self.logical_names
+            .retain(|(name, _)| !name.eq_ignore_ascii_case(&schema));
+        self.logical_names.push((schema, logical.into()));
+        self
+    }
+
+    /// Call `callback` before each database, table and the schema is
+    /// processed.
+    /// Returning [`ControlFlow::Break`] from the callback cancels hashing
+    /// with [`Error::Cancelled`](crate::Error::Cancelled).
+    ///
+    /// To cancel in the middle of a large table, interrupt the connection
+    /// through [`Connection::get_interrupt_handle`] instead, which also
+    /// results in [`Error::Cancelled`](crate::Error::Cancelled).
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use std::ops::ControlFlow;
+    /// # use sqlite_dbhash::{HashOptions, Object};
+    /// # use rusqlite::Connection;
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let conn = Connection::open("my_db.db")?;
+    ///     let hash = HashOptions::new()
+    ///         .on_progress(|object| {
+    ///             println!("hashing {object}");
+    ///             match object {
+    ///                 Object::Table(name) if name.starts_with("huge_") => ControlFlow::Break(()),
+    ///                 _ => ControlFlow::Continue(()),
+    ///             }
+    ///         })
+    ///         .hash(&conn)?;
+    ///     println!("{hash}");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Object) -> ControlFlow<()> + Send + Sync + 'static,
+    {
+        self.progress = Some(ProgressCallback(Arc::new(callback)));
+        self
+    }
+
+    /// Compute the SHA1 hash of database through a database connection `conn`
+    /// obtained from `rusqlite`.
+    pub fn hash(&self, conn: &Connection) -> Result<DbHash> {
+        self.hash_with::<Sha1>(conn)
+    }
+
+    /// Compute the hash of database with digest algorithm `D` instead of SHA1.
+    ///
+    /// The digest is fed the exact same byte stream as [`HashOptions::hash`],
+    /// so `hash_with::<Sha1>` agrees with the original dbhash utility program.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use sqlite_dbhash::HashOptions;
+    /// # use rusqlite::Connection;
+    /// use sha2::Sha256;
+    ///
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let conn = Connection::open("my_db.db")?;
+    ///     println!("{}", HashOptions::new().hash_with::<Sha256>(&conn)?);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn hash_with<D: Digest>(&self, conn: &Connection) -> Result<DbHash<D>> {
+        let mut sink = DigestSink::<D>::new();
+        self.visit(conn, &mut sink)?;
+        Ok(sink.finalize())
+    }
+
+    /// Traverse the database like [`HashOptions::hash`], but feed the
+    /// traversal events into `sink` instead of a digest.
+    pub fn visit<S: HashSink + ?Sized>(&self, conn: &Connection, sink: &mut S) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        let _span = span!(Level::TRACE, "dbhash").entered();
+
+        let schema = resolve_schema(conn, self.schema_name.as_deref().unwrap_or("main"))?;
+        check_tables(conn, slice::from_ref(&schema), self)?;
+        self.visit_schema(conn, &schema, sink)
+    }
+
+    /// Compute the SHA1 hash of each database attached to the connection, as
+    /// listed by `PRAGMA database_list`, and a root hash combining them.
+    ///
+    /// Each database is hashed on its own with these options, as if selected
+    /// with [`HashOptions::schema_name`], and named by its alias or the name
+    /// given with [`HashOptions::logical_name`]. The `temp` database is left
+    /// out, since it only shows up in the list once the connection has used
+    /// it; hash it separately with `schema_name("temp")` if needed.
+    ///
+    /// A row filter or a foreign key root only applies to the databases
+    /// having its table, and hashing fails with [`Error::NotFound`] if none of
+    /// them has it.
+    ///
+    /// Hashing fails with [`Error::DuplicateName`] if two databases end up
+    /// with the same name, such as two aliases given the same logical name,
+    /// or a logical name equal to the alias of another database.
+    pub fn hash_databases(&self, conn: &Connection) -> Result<DatabaseHashes> {
+        self.hash_databases_with::<Sha1>(conn)
+    }
+
+    /// Compute the hash of each database attached to the connection like
+    /// [`HashOptions::hash_databases`], with digest algorithm `D` instead of
+    /// SHA1.
+    pub fn hash_databases_with<D: Digest>(&self, conn: &Connection) -> Result<DatabaseHashes<D>> {
+        #[cfg(feature = "tracing")]
+        let _span = span!(Level::TRACE, "dbhash databases").entered();
+
+        let schemas = conn
+            .prepare("SELECT name FROM pragma_database_list WHERE name <> 'temp' ORDER BY seq")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| row.get::<_, String>(0))?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .map_err(Error::sqlite(&Object::DatabaseList))?;
+        let names = schemas
+            .iter()
+            .map(|schema| {
+                self.logical_names
+                    .iter()
+                    .find(|(alias, _)| alias.eq_ignore_ascii_case(schema))
+                    .map_or(schema.as_str(), |(_, logical)| logical.as_str())
+            })
+            .collect::<Vec<_>>();
+        for (i, name) in names.iter().enumerate() {
+            if names[..i]
+                .iter()
+                .any(|other| other.eq_ignore_ascii_case(name))
+            {
+                return Err(Error::DuplicateName {
+                    object: Object::Database((*name).to_owned()),
+                });
+            }
+        }
+
+        // A table only needs to exist in one of the databases
+        check_tables(conn, &schemas, self)?;
+
+        let mut databases = Vec::with_capacity(schemas.len());
+        for (schema, name) in schemas.iter().zip(names) {
+            let name = name.to_owned();
+            let object = Object::Database(name.clone());
+            if let Some(progress) = &self.progress
+                && progress.call(&object).is_break()
+            {
+                return Err(Error::Cancelled { object });
+            }
+
+            let mut sink = DigestSink::<D>::new();
+            self.visit_schema(conn, schema, &mut sink)?;
+            databases.push((name, sink.finalize()));
+        }
+        Ok(DatabaseHashes::new(databases))
+    }
+
+    /// Traverse the database attached as `schema`, named exactly as listed by
+    /// `PRAGMA database_list`, into `sink`.
+    fn visit_schema<S: HashSink + ?Sized>(
+        &self,
+        conn: &Connection,
+        schema: &str,
+        sink: &mut S,
+    ) -> Result<()> {
+        let traversal = Traversal::new(conn, self, schema)?;
+        if self.selection.contains(Selection::CONTENT) {
+            traversal.content(sink)?;
+        }
+
+        if self.selection.intersects(Selection::SCHEMA) {
+            traversal.schema(sink)?;
+            if !self.settings.is_empty() {
+                traversal.settings(sink)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl HashOptions {
+    /// Whether the columns of `table` must be listed explicitly, instead of
+    /// selected with `SELECT *`.
+    pub(crate) fn lists_columns_of(&self, table: &[u8]) -> bool {
+        self.column_policy != ColumnPolicy::Visible
+            || self.column_exclusions.iter().any(|e| e.applies_to(table))
+    }
+
+    /// The row filter on `table`, if any.
+    pub(crate) fn row_filter_of(&self, table: &[u8]) -> Option<&RowFilter> {
+        self.row_filters.iter().find(|f| f.applies_to(table))
+    }
+
+    /// Whether `column` of `table` is hashed.
+    pub(crate) fn hashes_column(&self, table: &[u8], column: &TableColumn) -> bool {
+        column.is_hashed(self.column_policy)
+            && !self
+                .column_exclusions
+                .iter()
+                .any(|e| e.excludes(table, &column.name))
+    }
+}
+
+/// Signature of the callback set with [`HashOptions::on_progress`].
+type ProgressFn = dyn Fn(&Object) -> ControlFlow<()> + Send + Sync;
+
+/// Callback set with [`HashOptions::on_progress`].
+#[derive(Clone)]
+pub(crate) struct ProgressCallback(Arc<ProgressFn>);
+
+impl ProgressCallback {
+    pub(crate) fn call(&self, object: &Object) -> ControlFlow<()> {
+        (self.0)(object)
+    }
+}
+
+impl Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback(..)")
+    }
+}
@@ -0,0 +1,171 @@
+//! Hashing the result of arbitrary queries.
+use digest::{Digest, Output};
+use rusqlite::{Connection, Params, Statement, types::ValueRef};
+use sha1::Sha1;
+#[cfg(feature = "tracing")]
+use tracing::{Level, span};
+
+use crate::{DbHash, DigestSink, Error, HashSink, Object, Result, traverse::visit_rows};
+
+/// Specify how the order of rows returned by a query affects the hash.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RowOrder {
+    /// Rows are hashed in the order they are returned, like table content
+    /// in [`dbhash`](crate::dbhash).
+    #[default]
+    Ordered,
+    /// Rows are hashed regardless of the order they are returned, for
+    /// queries without `ORDER BY`.
+    ///
+    /// Each row is hashed on its own, and the hash of the query is the hash
+    /// of the concatenated row hashes sorted in ascending order.
+    Unordered,
+}
+
+/// Compute the SHA1 hash of the result of query `sql` with `params`.
+///
+/// The values are encoded in the same way as table content in [`dbhash`](crate::dbhash),
+/// so hashing `SELECT * FROM "t"` with [`RowOrder::Ordered`] gives the same hash
+/// as hashing only the content of table `t`.
+///
+/// # Examples
+/// ```no_run
+/// # use sqlite_dbhash::{hash_sql, RowOrder};
+/// # use rusqlite::Connection;
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let conn = Connection::open("my_db.db")?;
+///     let before = hash_sql(
+///         &conn,
+///         "SELECT id, total FROM orders WHERE total > ?1",
+///         [100],
+///         RowOrder::Unordered,
+///     )?;
+///     let after = hash_sql(
+///         &conn,
+///         "SELECT id, total FROM orders WHERE NOT total <= ?1",
+///         [100],
+///         RowOrder::Unordered,
+///     )?;
+///     assert_eq!(before, after);
+///     Ok(())
+/// }
+/// ```
+pub fn hash_sql<P: Params>(
+    conn: &Connection,
+    sql: &str,
+    params: P,
+    order: RowOrder,
+) -> Result<DbHash> {
+    hash_sql_with::<Sha1, P>(conn, sql, params, order)
+}
+
+/// Compute the hash of the result of query `sql` like [`hash_sql`], but with
+/// digest algorithm `D` instead of SHA1.
+pub fn hash_sql_with<D: Digest, P: Params>(
+    conn: &Connection,
+    sql: &str,
+    params: P,
+    order: RowOrder,
+) -> Result<DbHash<D>> {
+    let mut stmt = conn.prepare(sql).map_err(Error::sqlite(&Object::Query))?;
+    hash_statement_with(&mut stmt, params, order)
+}
+
+/// Compute the SHA1 hash of the result of a prepared statement `stmt`
+/// executed with `params`, in the same way as [`hash_sql`].
+pub fn hash_statement<P: Params>(
+    stmt: &mut Statement<'_>,
+    params: P,
+    order: RowOrder,
+) -> Result<DbHash> {
+    hash_statement_with::<Sha1, P>(stmt, params, order)
+}
+
+/// Compute the hash of the result of a prepared statement `stmt` like
+/// [`hash_statement`], but with digest algorithm `D` instead of SHA1.
+pub fn hash_statement_with<D: Digest, P: Params>(
+    stmt: &mut Statement<'_>,
+    params: P,
+    order: RowOrder,
+) -> Result<DbHash<D>> {
+    match order {
+        RowOrder::Ordered => {
+            let mut sink = DigestSink::<D>::new();
+            visit_statement(stmt, params, &mut sink)?;
+            Ok(sink.finalize())
+        }
+        RowOrder::Unordered => {
+            let mut sink = UnorderedSink::<D>::default();
+            visit_statement(stmt, params, &mut sink)?;
+            Ok(sink.finalize())
+        }
+    }
+}
+
+/// Feed the rows of a prepared statement `stmt` executed with `params` into `sink`.
+///
+/// Only [`HashSink::begin_row`] and [`HashSink::value`] are called.
+pub fn visit_statement<S: HashSink + ?Sized, P: Params>(
+    stmt: &mut Statement<'_>,
+    params: P,
+    sink: &mut S,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = span!(
+        Level::TRACE,
+        "hash query",
+        sql = stmt.expanded_sql().as_deref()
+    )
+    .entered();
+
+    let rows = stmt.query(params).map_err(Error::sqlite(&Object::Query))?;
+    visit_rows(sink, rows).map_err(Error::sqlite(&Object::Query))
+}
+
+/// A sink hashing each row separately for [`RowOrder::Unordered`].
+struct UnorderedSink<D: Digest> {
+    rows: Vec<Output<D>>,
+    current: Option<DigestSink<D>>,
+}
+
+impl<D: Digest> Default for UnorderedSink<D> {
+    fn default() -> Self {
+        Self {
+            rows: Vec::new(),
+            current: None,
+        }
+    }
+}
+
+impl<D: Digest> UnorderedSink<D> {
+    fn finish_row(&mut self) {
+        if let Some(row) = self.current.take() {
+            self.rows.push(row.finalize().into_output());
+        }
+    }
+
+    fn finalize(mut self) -> DbHash<D> {
+        self.finish_row();
+        self.rows.sort_unstable();
+
+        let mut hasher = D::new();
+        for row in &self.rows {
+            hasher.update(row);
+        }
+        DbHash::from_output(hasher.finalize())
+    }
+}
+
+impl<D: Digest> HashSink for UnorderedSink<D> {
+    fn begin_row(&mut self) {
+        self.finish_row();
+        self.current = Some(DigestSink::new());
+    }
+
+    fn value(&mut self, value: ValueRef<'_>) {
+        self.current
+            .as_mut()
+            .expect("values are always preceded by a row")
+            .value(value);
+    }
+}
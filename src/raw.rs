@@ -0,0 +1,155 @@
+//! Running queries whose SQL text is not valid UTF-8, which `rusqlite`
+//! cannot prepare. The original dbhash utility program interpolates table
+//! names into the SQL text as raw bytes, so this is needed to hash tables
+//! whose name is not valid UTF-8.
+use std::{
+    ffi::{CStr, c_char, c_int},
+    ptr, slice,
+};
+
+use rusqlite::{
+    Connection, ffi,
+    types::{Value, ValueRef},
+};
+
+use crate::HashSink;
+
+/// A statement prepared through the C API, finalized on drop.
+struct RawStatement(*mut ffi::sqlite3_stmt);
+
+impl Drop for RawStatement {
+    fn drop(&mut self) {
+        // SAFETY: the statement was successfully prepared and is finalized only once.
+        unsafe { ffi::sqlite3_finalize(self.0) };
+    }
+}
+
+/// Run `sql` on `conn` and visit the rows, like
+/// [`visit_rows`](crate::traverse::visit_rows), binding `params` in order.
+pub(crate) fn visit_raw_query<S: HashSink + ?Sized>(
+    conn: &Connection,
+    sql: &[u8],
+    params: &[&Value],
+    sink: &mut S,
+) -> rusqlite::Result<()> {
+    let len = c_len(sql)?;
+
+    // SAFETY: the handle is only used while `conn` is borrowed, so it stays open.
+    let db = unsafe { conn.handle() };
+    let mut stmt = ptr::null_mut();
+    // SAFETY: `sql` is valid for `len` bytes, and `stmt` is a valid out pointer.
+    let rc = unsafe {
+        ffi::sqlite3_prepare_v2(
+            db,
+            sql.as_ptr().cast::<c_char>(),
+            len,
+            &mut stmt,
+            ptr::null_mut(),
+        )
+    };
+    if rc != ffi::SQLITE_OK {
+        return Err(last_error(db));
+    }
+    let stmt = RawStatement(stmt);
+
+    for (i, param) in (1..).zip(params) {
+        // SAFETY: the statement is valid until dropped, and SQLite copies
+        // text and blobs before returning.
+        let rc = unsafe {
+            match param {
+                Value::Null => ffi::sqlite3_bind_null(stmt.0, i),
+                Value::Integer(value) => ffi::sqlite3_bind_int64(stmt.0, i, *value),
+                Value::Real(value) => ffi::sqlite3_bind_double(stmt.0, i, *value),
+                Value::Text(value) => ffi::sqlite3_bind_text(
+                    stmt.0,
+                    i,
+                    value.as_ptr().cast::<c_char>(),
+                    c_len(value.as_bytes())?,
+                    ffi::SQLITE_TRANSIENT(),
+                ),
+                Value::Blob(value) => ffi::sqlite3_bind_blob(
+                    stmt.0,
+                    i,
+                    value.as_ptr().cast(),
+                    c_len(value)?,
+                    ffi::SQLITE_TRANSIENT(),
+                ),
+            }
+        };
+        if rc != ffi::SQLITE_OK {
+            return Err(last_error(db));
+        }
+    }
+
+    loop {
+        // SAFETY: the statement is valid until dropped.
+        match unsafe { ffi::sqlite3_step(stmt.0) } {
+            ffi::SQLITE_ROW => {
+                sink.begin_row();
+                // SAFETY: the statement has a row available.
+                let column_count = unsafe { ffi::sqlite3_column_count(stmt.0) };
+                for i in 0..column_count {
+                    // SAFETY: the column is in range, and the value is dropped
+                    // before the statement is stepped again.
+                    sink.value(unsafe { column_value(stmt.0, i) });
+                }
+            }
+            ffi::SQLITE_DONE => return Ok(()),
+            _ => return Err(last_error(db)),
+        }
+    }
+}
+
+/// Read column `i` of the current row of `stmt`.
+///
+/// # Safety
+/// `stmt` must have a row available and `i` must be in range. The returned
+/// value is only valid until `stmt` is stepped, reset or finalized.
+unsafe fn column_value<'a>(stmt: *mut ffi::sqlite3_stmt, i: c_int) -> ValueRef<'a> {
+    /// Build a slice from a possibly null pointer returned for an empty value.
+    unsafe fn bytes<'a>(data: *const u8, len: c_int) -> &'a [u8] {
+        if data.is_null() {
+            &[]
+        } else {
+            // SAFETY: SQLite guarantees `data` is valid for `len` bytes.
+            unsafe { slice::from_raw_parts(data, len as usize) }
+        }
+    }
+
+    // SAFETY: guaranteed by the caller. The pointer is always requested
+    // before the length, as recommended by SQLite.
+    unsafe {
+        match ffi::sqlite3_column_type(stmt, i) {
+            ffi::SQLITE_INTEGER => ValueRef::Integer(ffi::sqlite3_column_int64(stmt, i)),
+            ffi::SQLITE_FLOAT => ValueRef::Real(ffi::sqlite3_column_double(stmt, i)),
+            ffi::SQLITE_TEXT => {
+                let data = ffi::sqlite3_column_text(stmt, i);
+                ValueRef::Text(bytes(data, ffi::sqlite3_column_bytes(stmt, i)))
+            }
+            ffi::SQLITE_BLOB => {
+                let data = ffi::sqlite3_column_blob(stmt, i).cast::<u8>();
+                ValueRef::Blob(bytes(data, ffi::sqlite3_column_bytes(stmt, i)))
+            }
+            _ => ValueRef::Null,
+        }
+    }
+}
+
+/// Length of `bytes` as expected by the C API.
+fn c_len(bytes: &[u8]) -> rusqlite::Result<c_int> {
+    c_int::try_from(bytes.len())
+        .map_err(|_| rusqlite::Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_TOOBIG), None))
+}
+
+/// Build an error from the last failure on `db`.
+fn last_error(db: *mut ffi::sqlite3) -> rusqlite::Error {
+    // SAFETY: `db` is an open connection, and the message is copied before
+    // any other call on the connection.
+    unsafe {
+        let code = ffi::sqlite3_extended_errcode(db);
+        let message = CStr::from_ptr(ffi::sqlite3_errmsg(db))
+            .to_string_lossy()
+            .into_owned();
+        rusqlite::Error::SqliteFailure(ffi::Error::new(code), Some(message))
+    }
+}
@@ -0,0 +1,35 @@
+//! Rows of tables, for hashing only some of them.
+use rusqlite::types::Value;
+
+/// A predicate restricting the rows of one table that are hashed.
+#[derive(Clone, Debug)]
+pub(crate) struct RowFilter {
+    pub(crate) table: String,
+    pub(crate) predicate: String,
+    pub(crate) params: Vec<Value>,
+}
+
+impl RowFilter {
+    /// Whether the filter applies to `table`.
+    pub(crate) fn applies_to(&self, table: &[u8]) -> bool {
+        self.table.as_bytes().eq_ignore_ascii_case(table)
+    }
+}
+
+/// What to do with the tables lacking the tenant column when hashing the
+/// slice of one tenant, see [`HashOptions::tenant`](crate::HashOptions::tenant).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TenantPolicy {
+    /// Leave the content of such tables out of the hash
+    SkipTable,
+    /// Hash all rows of such tables, as shared by all tenants
+    IncludeTable,
+}
+
+/// Restriction of the rows of all tables to one tenant.
+#[derive(Clone, Debug)]
+pub(crate) struct TenantScope {
+    pub(crate) column: String,
+    pub(crate) value: Value,
+    pub(crate) policy: TenantPolicy,
+}
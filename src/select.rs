@@ -0,0 +1,418 @@
+//! Selection of what to hash.
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::{BitAnd, BitOr, BitOrAssign, Sub},
+};
+
+use rusqlite::{
+    Connection, ParamsFromIter, Row, params_from_iter,
+    types::{ToSqlOutput, ValueRef},
+};
+
+use crate::{Error, HashOptions, Object, Result, columns::quote_identifier};
+
+/// Specify what to hash, as a set of flags.
+///
+/// The presets [`Selection::SchemaAndContent`], [`Selection::SchemaOnly`] and
+/// [`Selection::ContentOnly`] imitate the function of command-line arguments
+/// `--schema-only` and `--without-schema` in the original dbhash utility
+/// program. The schema can be narrowed further to the kinds of objects whose
+/// changes matter, combining flags with `|`.
+///
+/// # Examples
+/// ```no_run
+/// # use sqlite_dbhash::{HashOptions, Selection};
+/// # use rusqlite::Connection;
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let conn = Connection::open("my_db.db")?;
+///     // Only changes to the definition of tables and views change the hash
+///     let hash = HashOptions::new()
+///         .selection(Selection::TABLES | Selection::VIEWS)
+///         .hash(&conn)?;
+///     println!("{hash}");
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Selection(u8);
+
+#[allow(non_upper_case_globals)]
+impl Selection {
+    /// Hash the content of tables
+    pub const CONTENT: Self = Self(1 << 0);
+    /// Hash the schema entries of tables
+    pub const TABLES: Self = Self(1 << 1);
+    /// Hash the schema entries of indexes
+    pub const INDEXES: Self = Self(1 << 2);
+    /// Hash the schema entries of views
+    pub const VIEWS: Self = Self(1 << 3);
+    /// Hash the schema entries of triggers
+    pub const TRIGGERS: Self = Self(1 << 4);
+    /// Hash the schema entries of all kinds of objects
+    pub const SCHEMA: Self = Self::TABLES
+        .union(Self::INDEXES)
+        .union(Self::VIEWS)
+        .union(Self::TRIGGERS);
+
+    /// Hash both schema and table content
+    pub const SchemaAndContent: Self = Self::SCHEMA.union(Self::CONTENT);
+    /// Only hash the schema, equivalent to `--schema-only`
+    pub const SchemaOnly: Self = Self::SCHEMA;
+    /// Only hash the table content, equivalent to `--without-schema`
+    pub const ContentOnly: Self = Self::CONTENT;
+
+    /// Flags with the `type` of the `sqlite_schema` entries they select.
+    const SCHEMA_TYPES: [(Self, &str); 4] = [
+        (Self::TABLES, "table"),
+        (Self::INDEXES, "index"),
+        (Self::VIEWS, "view"),
+        (Self::TRIGGERS, "trigger"),
+    ];
+
+    /// Select nothing.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Whether nothing is selected.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether everything in `other` is selected.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether anything in `other` is selected.
+    pub const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Select everything in either `self` or `other`.
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// A SQL condition on `sqlite_schema` selecting the kinds of objects
+    /// in the schema.
+    pub(crate) fn schema_condition(self) -> String {
+        if self.contains(Self::SCHEMA) {
+            // Do not filter at all, to agree with the original program.
+            return "1".to_owned();
+        }
+        let types = Self::SCHEMA_TYPES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, kind)| format!("'{kind}'"))
+            .collect::<Vec<_>>();
+        format!("type IN ({})", types.join(", "))
+    }
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self::SchemaAndContent
+    }
+}
+
+impl BitOr for Selection {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+impl BitOrAssign for Selection {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = self.union(rhs);
+    }
+}
+
+impl BitAnd for Selection {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl Sub for Selection {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 & !rhs.0)
+    }
+}
+
+impl fmt::Debug for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = [
+            (Self::CONTENT, "CONTENT"),
+            (Self::TABLES, "TABLES"),
+            (Self::INDEXES, "INDEXES"),
+            (Self::VIEWS, "VIEWS"),
+            (Self::TRIGGERS, "TRIGGERS"),
+        ]
+        .into_iter()
+        .filter(|(flag, _)| self.contains(*flag))
+        .map(|(_, name)| name)
+        .collect::<Vec<_>>();
+        if flags.is_empty() {
+            write!(f, "Selection(empty)")
+        } else {
+            write!(f, "Selection({})", flags.join(" | "))
+        }
+    }
+}
+
+/// A rule matching table names, used to include or exclude tables through
+/// [`HashOptions::include`] and [`HashOptions::exclude`].
+///
+/// All rules except `Regex`, which requires the `regex` feature, are
+/// evaluated by SQLite. Regular expressions are matched in Rust against the
+/// names listed from `sqlite_schema`.
+///
+/// # Examples
+/// ```no_run
+/// # use sqlite_dbhash::{HashOptions, TableMatcher};
+/// # use rusqlite::Connection;
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let conn = Connection::open("my_db.db")?;
+///     // Hash the `user_*` tables, but not `user_sessions`
+///     let hash = HashOptions::new()
+///         .include(TableMatcher::Glob("user_*".into()))
+///         .exclude(TableMatcher::Exact("user_sessions".into()))
+///         .hash(&conn)?;
+///     println!("{hash}");
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum TableMatcher {
+    /// Names that are LIKE the pattern, given as raw bytes that are not
+    /// necessarily valid UTF-8. LIKE ignores ASCII case, and uses `%` and `_`
+    /// as wildcards.
+    Like(Vec<u8>),
+    /// Names that are LIKE the pattern, where the escape character makes
+    /// the following `%` or `_` match literally, as in
+    /// `LIKE pattern ESCAPE escape`.
+    LikeEscape(String, char),
+    /// Names that match the GLOB pattern. GLOB is case-sensitive, and uses
+    /// `*`, `?` and `[...]` as wildcards.
+    Glob(String),
+    /// Names that match the regular expression anywhere. The expression is
+    /// matched against the raw bytes of the name, so it also applies to names
+    /// that are not valid UTF-8.
+    #[cfg(feature = "regex")]
+    Regex(regex::bytes::Regex),
+    /// The exact name, ignoring ASCII case like other names given to
+    /// [`HashOptions`].
+    Exact(String),
+}
+
+impl TableMatcher {
+    /// Render the rule as a SQL condition on `column`, pushing its parameters
+    /// into `params`. Returns `None` if the rule cannot be evaluated by SQLite.
+    fn render<'a>(&'a self, column: &str, params: &mut Vec<ToSqlOutput<'a>>) -> Option<String> {
+        Some(match self {
+            // Bind the raw bytes as TEXT, the same way the original dbhash
+            // utility program interpolates the pattern into the SQL text.
+            Self::Like(pattern) => format!("{column} LIKE ?{}", bind(params, pattern)),
+            Self::LikeEscape(pattern, escape) => {
+                let pattern = bind(params, pattern.as_bytes());
+                params.push(ToSqlOutput::Owned(escape.to_string().into()));
+                format!("{column} LIKE ?{pattern} ESCAPE ?{}", params.len())
+            }
+            Self::Glob(pattern) => format!("{column} GLOB ?{}", bind(params, pattern.as_bytes())),
+            #[cfg(feature = "regex")]
+            Self::Regex(_) => return None,
+            // NOCASE folds ASCII letters only, exactly like SQLite resolves
+            // identifiers.
+            Self::Exact(name) => {
+                format!(
+                    "{column} = ?{} COLLATE NOCASE",
+                    bind(params, name.as_bytes())
+                )
+            }
+        })
+    }
+
+    /// Whether the rule matches `name` without the help of SQLite.
+    #[cfg_attr(not(feature = "regex"), allow(unused_variables))]
+    fn is_match(&self, name: &[u8]) -> bool {
+        match self {
+            #[cfg(feature = "regex")]
+            Self::Regex(regex) => regex.is_match(name),
+            _ => false,
+        }
+    }
+}
+
+/// The selection of tables chosen by the options, evaluated partly by SQLite
+/// over a column holding table names, and partly in Rust.
+pub(crate) struct TableFilter<'a> {
+    options: &'a HashOptions,
+    /// Tables included through foreign keys
+    related: Option<&'a TableSet>,
+    /// SQL expressions of whether any include rule, and any exclude rule,
+    /// matches the column
+    columns: String,
+    params: Vec<ToSqlOutput<'a>>,
+}
+
+impl<'a> TableFilter<'a> {
+    /// Build the filter on `column` for `options`, including the `related`
+    /// tables if any.
+    pub(crate) fn new(
+        options: &'a HashOptions,
+        related: Option<&'a TableSet>,
+        column: &str,
+    ) -> Self {
+        let mut params = Vec::new();
+        let included = if options.includes.is_empty() && related.is_none() {
+            "1".to_owned()
+        } else {
+            any(&options.includes, column, &mut params)
+        };
+        let excluded = any(&options.excludes, column, &mut params);
+
+        Self {
+            options,
+            related,
+            columns: format!("{included}, {excluded}"),
+            params,
+        }
+    }
+
+    /// Two boolean result columns to select along with the table name, to be
+    /// passed to [`TableFilter::selects`]. The expressions refer to
+    /// parameters numbered from 1.
+    pub(crate) fn columns(&self) -> &str {
+        &self.columns
+    }
+
+    /// The parameters to bind for the result columns.
+    pub(crate) fn params(&self) -> ParamsFromIter<&Vec<ToSqlOutput<'a>>> {
+        params_from_iter(&self.params)
+    }
+
+    /// Whether the table `name` is selected, given a `row` holding the result
+    /// columns starting at index `columns`.
+    ///
+    /// A table is selected if any of the include rules matches, or there is
+    /// none, and none of the exclude rules does.
+    pub(crate) fn selects(
+        &self,
+        name: &[u8],
+        row: &Row<'_>,
+        columns: usize,
+    ) -> rusqlite::Result<bool> {
+        // The SQL expressions are NULL if the name itself is NULL
+        let included = row.get::<_, Option<bool>>(columns)?.unwrap_or(false)
+            || self.options.includes.iter().any(|m| m.is_match(name))
+            || self
+                .related
+                .is_some_and(|related| related.contains(&name.to_ascii_lowercase()));
+        let excluded = row.get::<_, Option<bool>>(columns + 1)?.unwrap_or(false)
+            || self.options.excludes.iter().any(|m| m.is_match(name));
+        Ok(included && !excluded)
+    }
+}
+
+/// Render a SQL expression of whether any of `matchers` evaluated by SQLite
+/// matches `column`.
+fn any<'a>(
+    matchers: &'a [TableMatcher],
+    column: &str,
+    params: &mut Vec<ToSqlOutput<'a>>,
+) -> String {
+    let conditions = matchers
+        .iter()
+        .filter_map(|matcher| matcher.render(column, params))
+        .collect::<Vec<_>>();
+    if conditions.is_empty() {
+        "0".to_owned()
+    } else {
+        format!("({})", conditions.join(" OR "))
+    }
+}
+
+/// Bind `text` as a TEXT parameter, returning its number.
+fn bind<'a>(params: &mut Vec<ToSqlOutput<'a>>, text: &'a [u8]) -> usize {
+    params.push(ToSqlOutput::Borrowed(ValueRef::Text(text)));
+    params.len()
+}
+
+/// Names of tables folded to ASCII lowercase, the way SQLite compares
+/// identifiers.
+pub(crate) type TableSet = HashSet<Vec<u8>>;
+
+/// Find the tables of database `schema` connected to any of `roots` through
+/// foreign keys, either referencing or referenced by them, directly or
+/// through other tables. Roots that are not tables of `schema` are ignored.
+pub(crate) fn foreign_key_closure(
+    conn: &Connection,
+    schema: &str,
+    roots: &[String],
+) -> Result<TableSet> {
+    let mut tables_stmt = conn
+        .prepare(&format!(
+            "SELECT name FROM {}.sqlite_schema WHERE type = 'table'",
+            quote_identifier(schema)
+        ))
+        .map_err(Error::sqlite(&Object::TableList))?;
+    let tables = tables_stmt
+        .query_map([], |row| Ok(row.get_ref(0)?.as_bytes()?.to_vec()))
+        .and_then(Iterator::collect::<rusqlite::Result<Vec<_>>>)
+        .map_err(Error::sqlite(&Object::TableList))?;
+
+    // Foreign keys as undirected edges between tables
+    let mut edges = HashMap::<Vec<u8>, Vec<Vec<u8>>>::new();
+    let mut fk_stmt = conn
+        .prepare(r#"SELECT "table" FROM pragma_foreign_key_list(?1, ?2)"#)
+        .map_err(Error::sqlite(&Object::TableList))?;
+    for table in &tables {
+        let object = Object::Table(String::from_utf8_lossy(table).into_owned());
+        let parents = fk_stmt
+            .query_map(
+                (
+                    ToSqlOutput::Borrowed(ValueRef::Text(table)),
+                    ToSqlOutput::Borrowed(ValueRef::Text(schema.as_bytes())),
+                ),
+                |row| Ok(row.get_ref(0)?.as_bytes()?.to_ascii_lowercase()),
+            )
+            .and_then(Iterator::collect::<rusqlite::Result<Vec<_>>>)
+            .map_err(Error::sqlite(&object))?;
+        let child = table.to_ascii_lowercase();
+        for parent in parents {
+            edges.entry(parent.clone()).or_default().push(child.clone());
+            edges.entry(child.clone()).or_default().push(parent);
+        }
+    }
+
+    let known = tables
+        .iter()
+        .map(|table| table.to_ascii_lowercase())
+        .collect::<TableSet>();
+    let mut pending = roots
+        .iter()
+        .map(|root| root.as_bytes().to_ascii_lowercase())
+        .filter(|root| known.contains(root))
+        .collect::<Vec<_>>();
+
+    let mut closure = TableSet::new();
+    while let Some(table) = pending.pop() {
+        if closure.contains(&table) {
+            continue;
+        }
+        if let Some(neighbors) = edges.get(&table) {
+            pending.extend(neighbors.iter().cloned());
+        }
+        closure.insert(table);
+    }
+    Ok(closure)
+}
@@ -0,0 +1,210 @@
+//! Consumers of the canonical stream traversed by dbhash.
+use digest::Digest;
+use rusqlite::types::ValueRef;
+#[cfg(feature = "tracing")]
+use tracing::trace;
+
+use crate::DbHash;
+
+/// A consumer of the events produced while traversing a database.
+///
+/// The library guarantees that events arrive in the same order as the
+/// original dbhash utility program reads the database: first the rows of
+/// each selected table, in the order of `name COLLATE nocase`, then the
+/// selected rows of `sqlite_schema`, in the same order. The options enabling
+/// more content add it after all tables and before the schema, in this
+/// order:
+///
+/// 1. the rows of each view with
+///    [`HashOptions::view_content`](crate::HashOptions::view_content), in
+///    order of name,
+/// 2. the rows of `sqlite_sequence` with
+///    [`HashOptions::sequence_content`](crate::HashOptions::sequence_content),
+/// 3. the rows of each statistics table with
+///    [`HashOptions::stat_content`](crate::HashOptions::stat_content), in
+///    order of name.
+///
+/// The database settings enabled by
+/// [`HashOptions::settings`](crate::HashOptions::settings) come last.
+///
+/// Only [`HashSink::value`] is required. The other events default to no-op,
+/// except [`HashSink::schema_entry`], which forwards the 4 columns of the
+/// entry to [`HashSink::value`] just like the original program hashes them,
+/// and [`HashSink::setting`], which forwards the name of the setting.
+/// [`DigestSink`] is the sink that computes the actual hash.
+///
+/// # Examples
+/// ```no_run
+/// # use sqlite_dbhash::{HashOptions, HashSink};
+/// # use rusqlite::{Connection, types::ValueRef};
+/// /// Count rows of each table
+/// #[derive(Default)]
+/// struct RowCounter(Vec<(String, usize)>);
+///
+/// impl HashSink for RowCounter {
+///     fn begin_table(&mut self, name: &[u8]) {
+///         self.0.push((String::from_utf8_lossy(name).into_owned(), 0));
+///     }
+///
+///     fn begin_row(&mut self) {
+///         if let Some((_, count)) = self.0.last_mut() {
+///             *count += 1;
+///         }
+///     }
+///
+///     fn value(&mut self, _value: ValueRef<'_>) {}
+/// }
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let conn = Connection::open("my_db.db")?;
+///     let mut counter = RowCounter::default();
+///     HashOptions::new().visit(&conn, &mut counter)?;
+///     println!("{:?}", counter.0);
+///     Ok(())
+/// }
+/// ```
+pub trait HashSink {
+    /// Called before the rows of table `name` in the content portion.
+    ///
+    /// It is also called before the rows of each view hashed with
+    /// [`HashOptions::view_content`](crate::HashOptions::view_content), which
+    /// are not told apart from tables by this event, as well as before the
+    /// internal tables enabled by the options.
+    ///
+    /// The name is given as raw bytes, since SQLite does not guarantee
+    /// table names to be valid UTF-8.
+    fn begin_table(&mut self, name: &[u8]) {
+        let _ = name;
+    }
+
+    /// Called before the values of each row in the content portion.
+    fn begin_row(&mut self) {}
+
+    /// Called for each value of a row, in column order.
+    fn value(&mut self, value: ValueRef<'_>);
+
+    /// Called before the entries of the schema portion.
+    fn begin_schema(&mut self) {}
+
+    /// Called for each `type, name, tbl_name, sql` row selected from `sqlite_schema`.
+    fn schema_entry(
+        &mut self,
+        kind: ValueRef<'_>,
+        name: ValueRef<'_>,
+        tbl_name: ValueRef<'_>,
+        sql: ValueRef<'_>,
+    ) {
+        self.value(kind);
+        self.value(name);
+        self.value(tbl_name);
+        self.value(sql);
+    }
+
+    /// Called before the database settings, if any are enabled.
+    fn begin_settings(&mut self) {}
+
+    /// Called before the rows returned by `PRAGMA name`, which are then
+    /// visited like the rows of a table.
+    fn setting(&mut self, name: &str) {
+        self.value(ValueRef::Text(name.as_bytes()));
+    }
+}
+
+impl<S: HashSink + ?Sized> HashSink for &mut S {
+    fn begin_table(&mut self, name: &[u8]) {
+        (**self).begin_table(name);
+    }
+
+    fn begin_row(&mut self) {
+        (**self).begin_row();
+    }
+
+    fn value(&mut self, value: ValueRef<'_>) {
+        (**self).value(value);
+    }
+
+    fn begin_schema(&mut self) {
+        (**self).begin_schema();
+    }
+
+    fn schema_entry(
+        &mut self,
+        kind: ValueRef<'_>,
+        name: ValueRef<'_>,
+        tbl_name: ValueRef<'_>,
+        sql: ValueRef<'_>,
+    ) {
+        (**self).schema_entry(kind, name, tbl_name, sql);
+    }
+
+    fn begin_settings(&mut self) {
+        (**self).begin_settings();
+    }
+
+    fn setting(&mut self, name: &str) {
+        (**self).setting(name);
+    }
+}
+
+/// A [`HashSink`] feeding values into digest `D` with the encoding of
+/// the original dbhash utility program.
+///
+/// Each value is encoded as a type tag followed by its content: `"0"` for
+/// NULL, `"1"` followed by 8 big-endian bytes for INTEGER, `"2"` followed by
+/// 8 big-endian bytes of the IEEE 754 representation for REAL, `"3"` followed
+/// by the UTF-8 bytes for TEXT, and `"4"` followed by the bytes for BLOB.
+#[derive(Clone, Debug, Default)]
+pub struct DigestSink<D> {
+    hasher: D,
+}
+
+impl<D: Digest> DigestSink<D> {
+    /// Create a sink with a fresh digest.
+    pub fn new() -> Self {
+        Self { hasher: D::new() }
+    }
+
+    /// Consume the sink and return the hash of everything fed so far.
+    pub fn finalize(self) -> DbHash<D> {
+        DbHash::from_output(self.hasher.finalize())
+    }
+}
+
+impl<D: Digest> HashSink for DigestSink<D> {
+    fn value(&mut self, value: ValueRef<'_>) {
+        let hasher = &mut self.hasher;
+        match value {
+            ValueRef::Null => {
+                hasher.update(b"0");
+                #[cfg(feature = "tracing")]
+                trace!("NULL");
+            }
+            ValueRef::Integer(value) => {
+                let bytes = value.to_be_bytes();
+                hasher.update(b"1");
+                hasher.update(bytes);
+                #[cfg(feature = "tracing")]
+                trace!("INT {value}");
+            }
+            ValueRef::Real(value) => {
+                let bytes = value.to_be_bytes();
+                hasher.update(b"2");
+                hasher.update(bytes);
+                #[cfg(feature = "tracing")]
+                trace!("FLOAT {value}");
+            }
+            ValueRef::Text(value) => {
+                hasher.update(b"3");
+                hasher.update(value);
+                #[cfg(feature = "tracing")]
+                trace!("TEXT {text}", text = String::from_utf8_lossy(value));
+            }
+            ValueRef::Blob(value) => {
+                hasher.update(b"4");
+                hasher.update(value);
+                #[cfg(feature = "tracing")]
+                trace!("BLOB ({len} bytes)", len = value.len());
+            }
+        }
+    }
+}
@@ -0,0 +1,531 @@
+//! Traversal of a database in the order of the original dbhash utility program.
+use std::cell::OnceCell;
+
+use rusqlite::{
+    Connection, OptionalExtension, Row, Rows, params_from_iter,
+    types::{Value, ValueRef},
+};
+#[cfg(feature = "tracing")]
+use tracing::{Level, span};
+
+use crate::{
+    Error, HashOptions, HashSink, Object, Result, Selection, TenantPolicy,
+    columns::{push_identifier, quote_identifier, structural_sql, table_columns},
+    raw::visit_raw_query,
+    select::{TableFilter, TableSet, foreign_key_closure},
+};
+
+/// State shared by the steps of one traversal of a database.
+pub(crate) struct Traversal<'a> {
+    conn: &'a Connection,
+    options: &'a HashOptions,
+    /// Name of the database to hash, as listed by `PRAGMA database_list`
+    schema: String,
+    /// Schema version when the traversal started
+    schema_version: i64,
+    /// Tables related to the foreign key roots, if any
+    related: Option<TableSet>,
+}
+
+impl<'a> Traversal<'a> {
+    /// Start a traversal of the database attached to `conn` as `schema`,
+    /// named exactly as listed by `PRAGMA database_list`.
+    pub(crate) fn new(
+        conn: &'a Connection,
+        options: &'a HashOptions,
+        schema: &str,
+    ) -> Result<Self> {
+        let schema = schema.to_owned();
+        let schema_version =
+            schema_version(conn, &schema).map_err(Error::sqlite(&Object::Schema))?;
+        let related = if options.foreign_key_roots.is_empty() {
+            None
+        } else {
+            Some(foreign_key_closure(
+                conn,
+                &schema,
+                &options.foreign_key_roots,
+            )?)
+        };
+        Ok(Self {
+            conn,
+            options,
+            schema,
+            schema_version,
+            related,
+        })
+    }
+
+    /// Report progress before processing `object`, and make sure the
+    /// schema has not changed so far.
+    fn check(&self, object: &Object) -> Result<()> {
+        if let Some(progress) = &self.options.progress
+            && progress.call(object).is_break()
+        {
+            return Err(Error::Cancelled {
+                object: object.clone(),
+            });
+        }
+        self.check_schema(object)
+    }
+
+    /// Make sure the schema has not changed since the traversal started.
+    fn check_schema(&self, object: &Object) -> Result<()> {
+        if schema_version(self.conn, &self.schema).map_err(Error::sqlite(object))?
+            != self.schema_version
+        {
+            return Err(Error::SchemaChanged {
+                object: object.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Visit the content of tables, and virtual tables, views and internal
+    /// tables if enabled, selected by the options.
+    pub(crate) fn content<S: HashSink + ?Sized>(&self, sink: &mut S) -> Result<()> {
+        let conn = self.conn;
+        self.check(&Object::TableList)?;
+
+        // Find all tables selected by the options, followed by the views and
+        // the internal tables.
+        let filter = TableFilter::new(self.options, self.related.as_ref(), "name");
+        let mut kinds = Vec::new();
+        // Shadow tables store the content of virtual tables in an internal
+        // layout, which is hashed through the virtual tables.
+        let non_shadow = format!(
+            "type = 'table' AND sql NOT LIKE 'CREATE VIRTUAL%'
+                AND name NOT IN (SELECT name FROM pragma_table_list
+                                  WHERE schema = '{}' AND type = 'shadow')",
+            self.schema.replace('\'', "''")
+        );
+        if self.options.virtual_table_content {
+            kinds.push(non_shadow.as_str());
+            kinds.push("type = 'table' AND sql LIKE 'CREATE VIRTUAL%'");
+        } else {
+            kinds.push("type = 'table' AND sql NOT LIKE 'CREATE VIRTUAL%'");
+        }
+        if self.options.view_content {
+            kinds.push("type = 'view'");
+        }
+        let mut names = vec!["name NOT LIKE 'sqlite_%'"];
+        if self.options.sequence_content {
+            names.push("name = 'sqlite_sequence'");
+        }
+        if self.options.stat_content {
+            names.push("name GLOB 'sqlite_stat*'");
+        }
+        let mut table_names_stmt = conn
+            .prepare(&format!(
+                "SELECT name, type = 'view', sql LIKE 'CREATE VIRTUAL%', {columns}
+                   FROM {schema}.sqlite_schema
+                  WHERE ({kinds})
+                    AND ({names})
+                  ORDER BY CASE
+                             WHEN type = 'view' THEN 1
+                             WHEN name = 'sqlite_sequence' THEN 2
+                             WHEN name GLOB 'sqlite_stat*' THEN 3
+                             ELSE 0
+                           END,
+                           name COLLATE nocase",
+                columns = filter.columns(),
+                schema = quote_identifier(&self.schema),
+                kinds = kinds.join(" OR "),
+                names = names.join(" OR ")
+            ))
+            .map_err(Error::sqlite(&Object::TableList))?;
+        let mut table_names = table_names_stmt
+            .query(filter.params())
+            .map_err(Error::sqlite(&Object::TableList))?;
+
+        while let Some(row) = table_names
+            .next()
+            .map_err(Error::sqlite(&Object::TableList))?
+        {
+            // Table names are not necessarily valid UTF-8
+            let name = row
+                .get_ref(0)
+                .and_then(|name| Ok(name.as_bytes()?))
+                .map_err(Error::sqlite(&Object::TableList))?;
+            let (is_view, is_virtual) = row
+                .get(1)
+                .and_then(|is_view| Ok((is_view, row.get(2)?)))
+                .map_err(Error::sqlite(&Object::TableList))?;
+            if !filter
+                .selects(name, row, 3)
+                .map_err(Error::sqlite(&Object::TableList))?
+            {
+                continue;
+            }
+            let lossy_name = String::from_utf8_lossy(name).into_owned();
+            let object = if is_view {
+                Object::View(lossy_name)
+            } else {
+                Object::Table(lossy_name)
+            };
+            self.check(&object)?;
+
+            // optional tracing
+            #[cfg(feature = "tracing")]
+            let _span =
+                span!(Level::TRACE, "hash table content", table = %String::from_utf8_lossy(name))
+                    .entered();
+
+            let (select_sql, params) = match self
+                .content_query(name, is_view || is_virtual)
+                .map_err(Error::sqlite(&object))?
+            {
+                ContentQuery::Skip => continue,
+                ContentQuery::NoColumns => {
+                    sink.begin_table(name);
+                    continue;
+                }
+                ContentQuery::Select { sql, params } => {
+                    sink.begin_table(name);
+                    (sql, params)
+                }
+            };
+            match String::from_utf8(select_sql) {
+                Ok(select_sql) => {
+                    let mut select_stmt =
+                        conn.prepare(&select_sql).map_err(Error::sqlite(&object))?;
+                    let rows = select_stmt
+                        .query(params_from_iter(&params))
+                        .map_err(Error::sqlite(&object))?;
+                    visit_rows(sink, rows).map_err(Error::sqlite(&object))?;
+                }
+                Err(e) => {
+                    visit_raw_query(conn, e.as_bytes(), &params, sink)
+                        .map_err(Error::sqlite(&object))?;
+                }
+            }
+        }
+
+        self.check_schema(&Object::TableList)
+    }
+
+    /// Visit the schema of tables selected by the options.
+    pub(crate) fn schema<S: HashSink + ?Sized>(&self, sink: &mut S) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        let _span = span!(Level::TRACE, "hash schema").entered();
+
+        let conn = self.conn;
+        let object = Object::Schema;
+        self.check(&object)?;
+
+        let filter = TableFilter::new(self.options, self.related.as_ref(), "tbl_name");
+        let mut table_info_stmt = conn
+            .prepare(&format!(
+                "SELECT type, name, tbl_name, sql, {columns} FROM {schema}.sqlite_schema
+                  WHERE {types}
+                  ORDER BY name COLLATE nocase",
+                columns = filter.columns(),
+                schema = quote_identifier(&self.schema),
+                types = self.options.selection.schema_condition()
+            ))
+            .map_err(Error::sqlite(&object))?;
+        let mut table_infos = table_info_stmt
+            .query(filter.params())
+            .map_err(Error::sqlite(&object))?;
+
+        sink.begin_schema();
+        while let Some(row) = table_infos.next().map_err(Error::sqlite(&object))? {
+            let selected = row
+                .get_ref(2)
+                .and_then(|tbl_name| Ok(tbl_name.as_bytes_or_null()?.unwrap_or_default()))
+                .and_then(|tbl_name| filter.selects(tbl_name, row, 4))
+                .map_err(Error::sqlite(&object))?;
+            if selected {
+                self.visit_schema_entry(sink, row)
+                    .map_err(Error::sqlite(&object))?;
+            }
+        }
+
+        self.check_schema(&object)
+    }
+
+    /// Visit the database settings enabled by the options, in order of name.
+    pub(crate) fn settings<S: HashSink + ?Sized>(&self, sink: &mut S) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        let _span = span!(Level::TRACE, "hash settings").entered();
+
+        let conn = self.conn;
+        sink.begin_settings();
+        for name in &self.options.settings {
+            let object = Object::Setting(name.clone());
+            self.check(&object)?;
+
+            let Some(sql) = self.setting_query(name).map_err(Error::sqlite(&object))? else {
+                return Err(Error::NotFound { object });
+            };
+            let mut stmt = conn.prepare(&sql).map_err(Error::sqlite(&object))?;
+            let rows = stmt.query([]).map_err(Error::sqlite(&object))?;
+            sink.setting(name);
+            visit_rows(sink, rows).map_err(Error::sqlite(&object))?;
+        }
+        Ok(())
+    }
+
+    /// Build the query reading setting `name` of the database, or `None` if
+    /// the pragma cannot be read without side effects.
+    fn setting_query(&self, name: &str) -> rusqlite::Result<Option<String>> {
+        // SQLite only provides the table-valued function of a pragma if it
+        // returns results without side effects, except for these
+        if NOT_SETTINGS.contains(&name) {
+            return Ok(None);
+        }
+        let function = format!("pragma_{name}");
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name, hidden FROM pragma_table_xinfo(?1)")?;
+        let columns = stmt
+            .query_map([&function], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        if columns.is_empty() {
+            return Ok(None);
+        }
+
+        let takes_schema = columns
+            .iter()
+            .any(|(column, hidden)| *hidden && column == "schema");
+        let sql = if takes_schema {
+            format!(
+                "SELECT * FROM {} WHERE schema = '{}'",
+                quote_identifier(&function),
+                self.schema.replace('\'', "''")
+            )
+        } else if self.schema == "main" {
+            format!("SELECT * FROM {}", quote_identifier(&function))
+        } else {
+            // Functions without a schema argument, like `pragma_user_version`,
+            // only read `main`, so run the same pragma on the database instead
+            format!(
+                "PRAGMA {}.{}",
+                quote_identifier(&self.schema),
+                quote_identifier(name)
+            )
+        };
+        Ok(Some(sql))
+    }
+
+    /// Build the query reading the content of table or view `name`, sorting
+    /// the rows if their order is not well-defined.
+    fn content_query(&self, name: &[u8], sort_rows: bool) -> rusqlite::Result<ContentQuery<'a>> {
+        let options = self.options;
+        let lists_columns = options.lists_columns_of(name);
+        let columns = if lists_columns || options.tenant.is_some() || sort_rows {
+            table_columns(self.conn, &self.schema, name)?
+        } else {
+            Vec::new()
+        };
+        let selected = columns
+            .iter()
+            .filter(|column| options.hashes_column(name, column))
+            .collect::<Vec<_>>();
+
+        let mut sql = b"SELECT ".to_vec();
+        if lists_columns {
+            if selected.is_empty() {
+                return Ok(ContentQuery::NoColumns);
+            }
+            for (i, column) in selected.iter().enumerate() {
+                if i > 0 {
+                    sql.extend_from_slice(b", ");
+                }
+                push_identifier(&mut sql, &column.name);
+            }
+        } else {
+            sql.push(b'*');
+        }
+        sql.extend_from_slice(b" FROM ");
+        push_identifier(&mut sql, self.schema.as_bytes());
+        sql.push(b'.');
+        push_identifier(&mut sql, name);
+
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        if let Some(filter) = options.row_filter_of(name) {
+            conditions.push(format!("({})", filter.predicate).into_bytes());
+            params.extend(&filter.params);
+        }
+        if let Some(tenant) = &options.tenant {
+            let has_column = columns
+                .iter()
+                .any(|column| column.name.eq_ignore_ascii_case(tenant.column.as_bytes()));
+            if has_column {
+                let mut condition = Vec::new();
+                push_identifier(&mut condition, tenant.column.as_bytes());
+                condition.extend_from_slice(b" = ?");
+                conditions.push(condition);
+                params.push(&tenant.value);
+            } else if tenant.policy == TenantPolicy::SkipTable {
+                return Ok(ContentQuery::Skip);
+            }
+        }
+        if !conditions.is_empty() {
+            sql.extend_from_slice(b" WHERE ");
+            sql.extend_from_slice(&conditions.join(&b" AND "[..]));
+        }
+
+        // The order of rows in a view or virtual table depends on the query
+        // plan or the implementation
+        if sort_rows && !selected.is_empty() {
+            let order = (1..=selected.len())
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            sql.extend_from_slice(format!(" ORDER BY {order}").as_bytes());
+        }
+
+        Ok(ContentQuery::Select { sql, params })
+    }
+
+    /// Visit one `type, name, tbl_name, sql` row of `sqlite_schema`.
+    fn visit_schema_entry<S: HashSink + ?Sized>(
+        &self,
+        sink: &mut S,
+        row: &Row<'_>,
+    ) -> rusqlite::Result<()> {
+        let (kind, name, tbl_name, sql) = (
+            row.get_ref(0)?,
+            row.get_ref(1)?,
+            row.get_ref(2)?,
+            row.get_ref(3)?,
+        );
+        let is_ordinary_table = kind.as_bytes_or_null()? == Some(b"table")
+            && sql
+                .as_bytes_or_null()?
+                .is_some_and(|sql| !sql.starts_with(b"CREATE VIRTUAL"));
+        if self.options.structural_schema && is_ordinary_table {
+            let tbl_name = tbl_name.as_bytes()?;
+            let columns = table_columns(self.conn, &self.schema, tbl_name)?;
+            let structure = structural_sql(
+                tbl_name,
+                columns
+                    .iter()
+                    .filter(|column| self.options.hashes_column(tbl_name, column)),
+            );
+            sink.schema_entry(
+                kind,
+                name,
+                ValueRef::Text(tbl_name),
+                ValueRef::Text(&structure),
+            );
+        } else {
+            sink.schema_entry(kind, name, tbl_name, sql);
+        }
+        Ok(())
+    }
+}
+
+/// Pragmas with a table-valued function that do work rather than read a
+/// setting: `optimize` may run `ANALYZE`, and the checks scan the database.
+const NOT_SETTINGS: [&str; 3] = ["integrity_check", "optimize", "quick_check"];
+
+/// How to read the content of a table.
+enum ContentQuery<'a> {
+    /// The table is not hashed at all
+    Skip,
+    /// Every column is excluded, so only the table itself is hashed
+    NoColumns,
+    /// Run `sql` with `params`
+    Select {
+        sql: Vec<u8>,
+        params: Vec<&'a Value>,
+    },
+}
+
+/// Read the schema version of database `schema`, which changes whenever
+/// its schema changes.
+pub(crate) fn schema_version(conn: &Connection, schema: &str) -> rusqlite::Result<i64> {
+    conn.query_row(
+        &format!("PRAGMA {}.schema_version", quote_identifier(schema)),
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Make sure each row filter and foreign key root of `options` names a table
+/// of at least one of the databases `schemas`, so that a misspelled name is
+/// not silently ignored. Row filters only matter if the content is hashed,
+/// and may also name a view.
+pub(crate) fn check_tables(
+    conn: &Connection,
+    schemas: &[String],
+    options: &HashOptions,
+) -> Result<()> {
+    let row_filters = if options.selection.contains(Selection::CONTENT) {
+        options.row_filters.as_slice()
+    } else {
+        &[]
+    };
+    let tables = row_filters
+        .iter()
+        .map(|filter| (&filter.table, "'table', 'view'"))
+        .chain(
+            options
+                .foreign_key_roots
+                .iter()
+                .map(|root| (root, "'table'")),
+        );
+    for (table, types) in tables {
+        let object = Object::Table(table.clone());
+        let mut exists = false;
+        for schema in schemas {
+            exists = conn
+                .query_row(
+                    &format!(
+                        "SELECT count(*) > 0 FROM {}.sqlite_schema
+                          WHERE type IN ({types}) AND name = ?1 COLLATE NOCASE",
+                        quote_identifier(schema)
+                    ),
+                    [table],
+                    |row| row.get::<_, bool>(0),
+                )
+                .map_err(Error::sqlite(&object))?;
+            if exists {
+                break;
+            }
+        }
+        if !exists {
+            return Err(Error::NotFound { object });
+        }
+    }
+    Ok(())
+}
+
+/// Find the name of the database attached as `name`, ignoring ASCII case.
+pub(crate) fn resolve_schema(conn: &Connection, name: &str) -> Result<String> {
+    let object = Object::Database(name.to_owned());
+    conn.query_row(
+        "SELECT name FROM pragma_database_list WHERE name = ?1 COLLATE NOCASE",
+        [name],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(Error::sqlite(&object))?
+    .ok_or(Error::NotFound { object })
+}
+
+/// Visit the result of one query
+pub(crate) fn visit_rows<S: HashSink + ?Sized>(
+    sink: &mut S,
+    mut rows: Rows<'_>,
+) -> rusqlite::Result<()> {
+    let column_count_cell = OnceCell::new();
+
+    while let Some(row) = rows.next()? {
+        // Need to lazily get column count here after stepping at least once
+        // to handle shcema change between creation of statement and the execution
+        // of the statement
+        let column_count = column_count_cell.get_or_init(|| row.as_ref().column_count());
+        sink.begin_row();
+        for i in 0..*column_count {
+            sink.value(row.get_ref(i)?);
+        }
+    }
+
+    Ok(())
+}
@@ -1,12 +1,15 @@
+// Each test crate only uses part of the harness
+#![allow(dead_code)]
+
 use std::{
     env, fs,
     path::{Path, PathBuf},
     process::Command,
 };
 
-use anyhow::{Context, ensure};
+use anyhow::{Context, bail, ensure};
 use rusqlite::Connection;
-use sqlite_dbhash::{Selection, dbhash};
+use sqlite_dbhash::{HashOptions, Selection, dbhash};
 
 pub enum Step {
     /// The test harness executes the provided SQL and performs
@@ -15,7 +18,7 @@ pub enum Step {
     /// The test harness performs a comparison specified by
     /// the table pattern and what to compare.
     Compare {
-        table_pattern: Option<&'static str>,
+        table_pattern: Option<&'static [u8]>,
         selection: Selection,
     },
 }
@@ -28,6 +31,12 @@ impl Step {
 
     /// Constructor to perform custom comparison for a step
     pub fn compare(table_pattern: Option<&'static str>, selection: Selection) -> Self {
+        Self::compare_bytes(table_pattern.map(str::as_bytes), selection)
+    }
+
+    /// Constructor to perform custom comparison with a table pattern
+    /// that is not necessarily valid UTF-8
+    pub fn compare_bytes(table_pattern: Option<&'static [u8]>, selection: Selection) -> Self {
         Self::Compare {
             table_pattern,
             selection,
@@ -104,11 +113,19 @@ fn full_compare_dbhash(db_file: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Open an in-memory database populated by `sql`.
+pub fn open_with(sql: &str) -> Connection {
+    let conn = Connection::open_in_memory().expect("failed to open database");
+    conn.execute_batch(sql)
+        .expect("failed to populate database");
+    conn
+}
+
 /// Represent a type that can hash a database
 pub trait DbHasher {
     fn dbhash(
         db_file: &Path,
-        table_pattern: Option<&str>,
+        table_pattern: Option<&[u8]>,
         selection: Selection,
     ) -> anyhow::Result<String>;
 }
@@ -119,13 +136,19 @@ pub struct StockHasher;
 impl DbHasher for StockHasher {
     fn dbhash(
         db_file: &Path,
-        table_pattern: Option<&str>,
+        table_pattern: Option<&[u8]>,
         selection: Selection,
     ) -> anyhow::Result<String> {
         let mut cmd = Command::new(env!("DBHASH_PATH"));
 
         if let Some(table_pattern) = table_pattern {
-            cmd.args(["--like", table_pattern]);
+            #[cfg(unix)]
+            let table_pattern =
+                <std::ffi::OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(table_pattern);
+            #[cfg(not(unix))]
+            let table_pattern = str::from_utf8(table_pattern)
+                .context("non UTF-8 pattern can only be passed on unix")?;
+            cmd.arg("--like").arg(table_pattern);
         }
 
         match selection {
@@ -136,6 +159,7 @@ impl DbHasher for StockHasher {
             Selection::ContentOnly => {
                 cmd.arg("--without-schema");
             }
+            _ => bail!("dbhash does not support {selection:?}"),
         }
 
         cmd.arg(db_file);
@@ -165,13 +189,20 @@ pub struct LibHasher;
 impl DbHasher for LibHasher {
     fn dbhash(
         db_file: &Path,
-        table_pattern: Option<&str>,
+        table_pattern: Option<&[u8]>,
         selection: Selection,
     ) -> anyhow::Result<String> {
-        let mut conn = Connection::open(db_file)
+        let conn = Connection::open(db_file)
             .with_context(|| format!("failed to open {}", db_file.display()))?;
-        dbhash(&mut conn, table_pattern, selection)
-            .map(hex::encode)
-            .map_err(Into::into)
+        let hash = match table_pattern.map(|bytes| (bytes, str::from_utf8(bytes))) {
+            None => dbhash(&conn, None, selection),
+            Some((_, Ok(table_pattern))) => dbhash(&conn, Some(table_pattern), selection),
+            // Only patterns that are not valid UTF-8 need the options
+            Some((table_pattern, Err(_))) => HashOptions::new()
+                .selection(selection)
+                .table_pattern_bytes(table_pattern)
+                .hash(&conn),
+        };
+        hash.map(|hash| hash.to_string()).map_err(Into::into)
     }
 }
@@ -0,0 +1,274 @@
+use std::ops::ControlFlow;
+
+use rusqlite::Connection;
+use sqlite_dbhash::{ConnectionExt, Error, HashOptions, Object, Selection};
+
+use crate::harness::open_with;
+
+mod harness;
+
+const MAIN: &str = "
+    CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+    INSERT INTO users VALUES (1, 'main');
+";
+
+const AUX: &str = "
+    CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, email TEXT);
+    CREATE TABLE orders (id INT, user_id INT REFERENCES users (id));
+    CREATE TABLE logs (line TEXT);
+    CREATE VIEW user_names AS SELECT name FROM users;
+    INSERT INTO users VALUES (1, 'aux', 'aux@example.com'), (2, 'other', NULL);
+    INSERT INTO orders VALUES (1, 2);
+    INSERT INTO logs VALUES ('x');
+";
+
+/// Open a connection with `MAIN` and `AUX` attached under each of `aliases`,
+/// in order.
+fn open_attached(aliases: &[&str]) -> Connection {
+    let conn = open_with(MAIN);
+    for alias in aliases {
+        conn.execute(&format!("ATTACH DATABASE ':memory:' AS {alias}"), [])
+            .expect("failed to attach database");
+        conn.execute_batch(
+            &AUX.replace("CREATE TABLE ", &format!("CREATE TABLE {alias}."))
+                .replace("CREATE VIEW ", &format!("CREATE VIEW {alias}."))
+                .replace("INSERT INTO ", &format!("INSERT INTO {alias}.")),
+        )
+        .expect("failed to populate database");
+    }
+    conn
+}
+
+#[test]
+pub fn test_attach_alias() {
+    let conn = open_attached(&["aux"]);
+    let alone = open_with(AUX);
+
+    assert_eq!(
+        HashOptions::new()
+            .schema_name("aux")
+            .hash(&conn)
+            .expect("failed to hash"),
+        alone.dbhash().expect("failed to hash")
+    );
+    // The alias is matched ignoring case
+    assert_eq!(
+        HashOptions::new()
+            .schema_name("AUX")
+            .hash(&conn)
+            .expect("failed to hash"),
+        alone.dbhash().expect("failed to hash")
+    );
+    // The main database is unaffected by the attached one
+    assert_eq!(
+        conn.dbhash().expect("failed to hash"),
+        open_with(MAIN).dbhash().expect("failed to hash")
+    );
+
+    // Columns and foreign keys are looked up in the attached database
+    let options = HashOptions::new()
+        .foreign_key_closure("orders")
+        .exclude_column("users", "email")
+        .structural_schema(true)
+        .view_content(true);
+    assert_eq!(
+        options
+            .clone()
+            .schema_name("aux")
+            .hash(&conn)
+            .expect("failed to hash"),
+        options.hash(&alone).expect("failed to hash")
+    );
+}
+
+#[test]
+pub fn test_attach_temp() {
+    let conn = open_with(MAIN);
+    conn.execute_batch(
+        "
+        CREATE TEMP TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+        INSERT INTO temp.users VALUES (1, 'temp');
+        ",
+    )
+    .expect("failed to populate database");
+
+    // Unqualified names resolve to the temp table first, but the main
+    // database is still hashed on its own
+    assert_eq!(
+        conn.dbhash().expect("failed to hash"),
+        open_with(MAIN).dbhash().expect("failed to hash")
+    );
+    assert_eq!(
+        HashOptions::new()
+            .schema_name("temp")
+            .hash(&conn)
+            .expect("failed to hash"),
+        open_with(&MAIN.replace("'main'", "'temp'"))
+            .dbhash()
+            .expect("failed to hash")
+    );
+}
+
+#[test]
+pub fn test_attach_unknown() {
+    let conn = open_with(MAIN);
+    assert!(matches!(
+        HashOptions::new().schema_name("nope").hash(&conn),
+        Err(Error::NotFound { object: Object::Database(name) }) if name == "nope"
+    ));
+}
+
+#[test]
+pub fn test_attach_databases() {
+    let conn = open_attached(&["aux1", "aux2"]);
+    conn.execute_batch("CREATE TEMP TABLE scratch (x)")
+        .expect("failed to populate database");
+    let hashes = HashOptions::new()
+        .hash_databases(&conn)
+        .expect("failed to hash");
+
+    // Each database hashes like on its own, and temp is left out
+    let aux = open_with(AUX).dbhash().expect("failed to hash");
+    let main = open_with(MAIN).dbhash().expect("failed to hash");
+    assert_eq!(
+        hashes.iter().collect::<Vec<_>>(),
+        [("aux1", &aux), ("aux2", &aux), ("main", &main)]
+    );
+    assert_eq!(hashes.get("main"), Some(&main));
+    assert_eq!(hashes.get("temp"), None);
+
+    // The root does not depend on the order of attachment
+    let reversed = HashOptions::new()
+        .hash_databases(&open_attached(&["aux2", "aux1"]))
+        .expect("failed to hash");
+    assert_eq!(reversed.root(), hashes.root());
+    assert_ne!(
+        HashOptions::new()
+            .hash_databases(&open_attached(&["aux1", "aux3"]))
+            .expect("failed to hash")
+            .root(),
+        hashes.root()
+    );
+
+    // Logical names replace the aliases
+    let named = HashOptions::new()
+        .logical_name("AUX1", "archive")
+        .logical_name("aux2", "reports");
+    let renamed = named
+        .clone()
+        .logical_name("aux3", "archive")
+        .logical_name("aux4", "reports")
+        .hash_databases(&open_attached(&["aux4", "aux3"]))
+        .expect("failed to hash");
+    assert_eq!(
+        named.hash_databases(&conn).expect("failed to hash"),
+        renamed
+    );
+    assert_eq!(renamed.get("archive"), Some(&aux));
+
+    // Each database is reported to the progress callback
+    assert!(matches!(
+        HashOptions::new()
+            .on_progress(|object| match object {
+                Object::Database(name) if name == "aux2" => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            })
+            .hash_databases(&conn),
+        Err(Error::Cancelled { object: Object::Database(name) }) if name == "aux2"
+    ));
+}
+
+#[test]
+pub fn test_attach_databases_row_filter() {
+    let conn = open_attached(&["aux"]);
+    conn.execute_batch(
+        "
+        CREATE TABLE main.notes (line TEXT);
+        INSERT INTO main.notes VALUES ('a'), ('b');
+        ",
+    )
+    .expect("failed to populate database");
+
+    // The filter applies to the databases having the table
+    let options = HashOptions::new().row_filter("notes", "line = ?", ["a".to_owned()]);
+    let hashes = options.hash_databases(&conn).expect("failed to hash");
+    assert_eq!(
+        hashes.get("aux"),
+        Some(&open_with(AUX).dbhash().expect("failed to hash"))
+    );
+    assert_eq!(
+        hashes.get("main"),
+        Some(&options.hash(&conn).expect("failed to hash"))
+    );
+    assert_ne!(
+        hashes.get("main"),
+        Some(&conn.dbhash().expect("failed to hash"))
+    );
+
+    // A table missing from every database is only an error for the content
+    let missing = HashOptions::new().row_filter("nope", "1", Vec::<String>::new());
+    assert!(matches!(
+        missing.hash_databases(&conn),
+        Err(Error::NotFound { object: Object::Table(name) }) if name == "nope"
+    ));
+    assert_eq!(
+        missing
+            .selection(Selection::SchemaOnly)
+            .hash_databases(&conn)
+            .expect("failed to hash"),
+        HashOptions::new()
+            .selection(Selection::SchemaOnly)
+            .hash_databases(&conn)
+            .expect("failed to hash")
+    );
+}
+
+#[test]
+pub fn test_attach_databases_foreign_key_closure() {
+    let conn = open_attached(&["aux"]);
+
+    // The root only selects tables in the databases having it
+    let options = HashOptions::new().foreign_key_closure("orders");
+    let hashes = options.hash_databases(&conn).expect("failed to hash");
+    assert_eq!(
+        hashes.get("aux"),
+        Some(&options.hash(&open_with(AUX)).expect("failed to hash"))
+    );
+    assert_eq!(
+        hashes.get("main"),
+        Some(&open_with("").dbhash().expect("failed to hash"))
+    );
+
+    assert!(matches!(
+        HashOptions::new()
+            .foreign_key_closure("nope")
+            .hash_databases(&conn),
+        Err(Error::NotFound { object: Object::Table(name) }) if name == "nope"
+    ));
+}
+
+#[test]
+pub fn test_attach_duplicate_names() {
+    let conn = open_attached(&["aux1", "aux2"]);
+    assert!(matches!(
+        HashOptions::new()
+            .logical_name("aux1", "archive")
+            .logical_name("aux2", "archive")
+            .hash_databases(&conn),
+        Err(Error::DuplicateName { object: Object::Database(name) }) if name == "archive"
+    ));
+    assert!(matches!(
+        HashOptions::new()
+            .logical_name("aux1", "Main")
+            .hash_databases(&conn),
+        Err(Error::DuplicateName { object: Object::Database(name) }) if name == "Main"
+    ));
+
+    // Swapping names is fine
+    let swapped = HashOptions::new()
+        .logical_name("aux1", "aux2")
+        .logical_name("aux2", "aux1")
+        .hash_databases(&conn)
+        .expect("failed to hash");
+    assert_eq!(swapped.len(), 3);
+}
@@ -0,0 +1,252 @@
+use rusqlite::types::ValueRef;
+use sqlite_dbhash::{
+    ColumnPolicy, ConnectionExt, HashOptions, HashSink, RowOrder, Selection, hash_sql,
+};
+
+use crate::harness::open_with;
+
+mod harness;
+
+#[test]
+pub fn test_columns_exclude() {
+    let replica = |last_seen: i64| {
+        open_with(&format!(
+            "
+            CREATE TABLE users (id INT, last_seen INT, name TEXT, updated_at INT);
+            CREATE TABLE posts (id INT, body TEXT, Updated_At INT);
+            INSERT INTO users VALUES (1, {last_seen}, 'alice', {last_seen});
+            INSERT INTO posts VALUES (1, 'hello', {last_seen});
+            "
+        ))
+    };
+    let options = HashOptions::new()
+        .exclude_column("USERS", "last_seen")
+        .exclude_column_everywhere("updated_at")
+        .selection(Selection::ContentOnly);
+
+    let hash = options.clone().hash(&replica(100)).expect("failed to hash");
+    assert_eq!(options.hash(&replica(200)).expect("failed to hash"), hash);
+    assert_ne!(
+        replica(100).content_hash().expect("failed to hash"),
+        replica(200).content_hash().expect("failed to hash")
+    );
+
+    // The remaining columns are hashed in declared order
+    assert_eq!(
+        hash,
+        open_with(
+            "
+            CREATE TABLE users (id INT, name TEXT);
+            CREATE TABLE posts (id INT, body TEXT);
+            INSERT INTO users VALUES (1, 'alice');
+            INSERT INTO posts VALUES (1, 'hello');
+            "
+        )
+        .content_hash()
+        .expect("failed to hash")
+    );
+}
+
+#[test]
+pub fn test_columns_exclude_all() {
+    let conn = open_with(
+        "
+        CREATE TABLE t (updated_at INT);
+        CREATE TABLE \"we\"\"ird\" (id INT, updated_at INT);
+        INSERT INTO t VALUES (1), (2);
+        INSERT INTO \"we\"\"ird\" VALUES (1, 2);
+        ",
+    );
+
+    assert_eq!(
+        HashOptions::new()
+            .exclude_column_everywhere("updated_at")
+            .selection(Selection::ContentOnly)
+            .hash(&conn)
+            .expect("failed to hash"),
+        open_with(
+            "
+            CREATE TABLE \"we\"\"ird\" (id INT);
+            INSERT INTO \"we\"\"ird\" VALUES (1);
+            ",
+        )
+        .content_hash()
+        .expect("failed to hash")
+    );
+}
+
+#[test]
+pub fn test_columns_structural_schema() {
+    let options = HashOptions::new()
+        .exclude_column("t", "updated_at")
+        .structural_schema(true)
+        .selection(Selection::SchemaOnly);
+    let hash = |sql| options.hash(&open_with(sql)).expect("failed to hash");
+
+    let expected =
+        hash("CREATE TABLE t (id INTEGER NOT NULL, name TEXT DEFAULT 'x', PRIMARY KEY (id));");
+    // Formatting and excluded columns do not matter
+    assert_eq!(
+        hash(
+            "CREATE TABLE t (
+                id   INTEGER PRIMARY KEY NOT NULL,
+                updated_at INT,
+                name TEXT DEFAULT 'x'
+            );"
+        ),
+        expected
+    );
+    // The structure does
+    for sql in [
+        "CREATE TABLE t (id INTEGER NOT NULL, name TEXT DEFAULT 'y', PRIMARY KEY (id));",
+        "CREATE TABLE t (id INTEGER, name TEXT DEFAULT 'x', PRIMARY KEY (id));",
+        "CREATE TABLE t (id INTEGER NOT NULL, name TEXT DEFAULT 'x');",
+        "CREATE TABLE t (name TEXT DEFAULT 'x', id INTEGER NOT NULL, PRIMARY KEY (id));",
+    ] {
+        assert_ne!(hash(sql), expected);
+    }
+
+    // The SQL text is hashed as usual without structural hashing
+    let conn = open_with("CREATE TABLE t (id INT, updated_at INT);");
+    assert_eq!(
+        HashOptions::new()
+            .exclude_column("t", "updated_at")
+            .hash(&conn)
+            .expect("failed to hash"),
+        conn.dbhash().expect("failed to hash")
+    );
+}
+
+#[test]
+pub fn test_columns_generated() {
+    let conn = open_with(
+        "
+        CREATE TABLE t (
+            a INT,
+            doubled INT GENERATED ALWAYS AS (a * 2) VIRTUAL,
+            b TEXT,
+            upper_b TEXT GENERATED ALWAYS AS (upper(b)) STORED
+        );
+        INSERT INTO t (a, b) VALUES (1, 'x'), (2, 'y');
+        ",
+    );
+    let hash = |policy| {
+        HashOptions::new()
+            .column_policy(policy)
+            .selection(Selection::ContentOnly)
+            .hash(&conn)
+            .expect("failed to hash")
+    };
+
+    let visible = conn.content_hash().expect("failed to hash");
+    assert_eq!(hash(ColumnPolicy::Visible), visible);
+    assert_eq!(hash(ColumnPolicy::IncludeHidden), visible);
+    assert_eq!(
+        hash(ColumnPolicy::ExcludeGenerated),
+        open_with(
+            "
+            CREATE TABLE t (a INT, b TEXT);
+            INSERT INTO t VALUES (1, 'x'), (2, 'y');
+            "
+        )
+        .content_hash()
+        .expect("failed to hash")
+    );
+
+    // Generated columns are also left out of the structural schema
+    assert_eq!(
+        HashOptions::new()
+            .column_policy(ColumnPolicy::ExcludeGenerated)
+            .structural_schema(true)
+            .selection(Selection::SchemaOnly)
+            .hash(&conn)
+            .expect("failed to hash"),
+        HashOptions::new()
+            .structural_schema(true)
+            .selection(Selection::SchemaOnly)
+            .hash(&open_with("CREATE TABLE t (a INT, b TEXT);"))
+            .expect("failed to hash")
+    );
+}
+
+/// Count the values in each row hashed.
+#[derive(Default)]
+struct RowWidths(Vec<usize>);
+
+impl HashSink for RowWidths {
+    fn begin_row(&mut self) {
+        self.0.push(0);
+    }
+
+    fn value(&mut self, _value: ValueRef<'_>) {
+        if let Some(width) = self.0.last_mut() {
+            *width += 1;
+        }
+    }
+}
+
+#[test]
+pub fn test_columns_hidden() {
+    let conn = open_with(
+        "
+        CREATE VIRTUAL TABLE docs USING fts5 (title, body);
+        INSERT INTO docs VALUES ('a', 'first doc'), ('b', 'second doc');
+        ",
+    );
+    let options = |policy| {
+        HashOptions::new()
+            .virtual_table_content(true)
+            .column_policy(policy)
+            .table_name("docs")
+            .selection(Selection::ContentOnly)
+    };
+
+    assert_eq!(
+        options(ColumnPolicy::Visible)
+            .hash(&conn)
+            .expect("failed to hash"),
+        hash_sql(
+            &conn,
+            "SELECT title, body FROM docs ORDER BY 1, 2",
+            [],
+            RowOrder::Ordered
+        )
+        .expect("failed to hash")
+    );
+    // FTS5 has the hidden columns `docs` and `rank`, which depend on the
+    // query and are never hashed
+    for policy in [
+        ColumnPolicy::Visible,
+        ColumnPolicy::ExcludeGenerated,
+        ColumnPolicy::IncludeHidden,
+    ] {
+        let mut widths = RowWidths::default();
+        options(policy)
+            .visit(&conn, &mut widths)
+            .expect("failed to visit");
+        assert_eq!(widths.0, [2, 2]);
+    }
+    assert_eq!(
+        options(ColumnPolicy::IncludeHidden)
+            .hash(&conn)
+            .expect("failed to hash"),
+        options(ColumnPolicy::IncludeHidden)
+            .hash(&conn)
+            .expect("failed to hash")
+    );
+
+    // FTS4 also has the hidden columns `docid` and `__langid`, which hold data
+    conn.execute_batch(
+        "
+        DROP TABLE docs;
+        CREATE VIRTUAL TABLE docs USING fts4 (title, body);
+        INSERT INTO docs VALUES ('a', 'first doc');
+        ",
+    )
+    .expect("failed to populate database");
+    let mut widths = RowWidths::default();
+    options(ColumnPolicy::IncludeHidden)
+        .visit(&conn, &mut widths)
+        .expect("failed to visit");
+    assert_eq!(widths.0, [4]);
+}
@@ -0,0 +1,115 @@
+use std::{env, fs, ops::ControlFlow, path::PathBuf};
+
+use rusqlite::{Connection, InterruptHandle, functions::FunctionFlags, types::ValueRef};
+use sqlite_dbhash::{Error, HashOptions, HashSink, Object};
+
+fn populated_db() -> Connection {
+    let conn = Connection::open_in_memory().expect("failed to open database");
+    conn.execute_batch(
+        "
+        CREATE TABLE a (intval INT);
+        CREATE TABLE b (intval INT);
+        INSERT INTO a VALUES (1), (2);
+        INSERT INTO b VALUES (3), (4);
+        ",
+    )
+    .expect("failed to populate database");
+    conn
+}
+
+#[test]
+pub fn test_error_cancelled_by_progress() {
+    let conn = populated_db();
+
+    let result = HashOptions::new()
+        .on_progress(|object| match object {
+            Object::Table(name) if name == "b" => ControlFlow::Break(()),
+            _ => ControlFlow::Continue(()),
+        })
+        .hash(&conn);
+    assert!(matches!(
+        result,
+        Err(Error::Cancelled { object: Object::Table(name) }) if name == "b"
+    ));
+}
+
+/// Interrupt the connection when the first row is visited.
+struct Interrupter(InterruptHandle);
+
+impl HashSink for Interrupter {
+    fn begin_row(&mut self) {
+        self.0.interrupt();
+    }
+
+    fn value(&mut self, _value: ValueRef<'_>) {}
+}
+
+#[test]
+pub fn test_error_cancelled_by_interrupt() {
+    let conn = populated_db();
+
+    let result = HashOptions::new().visit(&conn, &mut Interrupter(conn.get_interrupt_handle()));
+    assert!(matches!(
+        result,
+        Err(Error::Cancelled { object: Object::Table(name) }) if name == "a"
+    ));
+}
+
+#[test]
+pub fn test_error_table_context() {
+    let conn = Connection::open_in_memory().expect("failed to open database");
+    conn.create_scalar_function("double", 1, FunctionFlags::SQLITE_DETERMINISTIC, |ctx| {
+        Ok(ctx.get::<i64>(0)? * 2)
+    })
+    .expect("failed to create function");
+    conn.execute_batch(
+        "
+        CREATE TABLE a (intval INT);
+        CREATE TABLE b (intval INT, doubled INT AS (double(intval)));
+        INSERT INTO b (intval) VALUES (1);
+        ",
+    )
+    .expect("failed to populate database");
+    conn.remove_function("double", 1)
+        .expect("failed to remove function");
+
+    let error = HashOptions::new()
+        .hash(&conn)
+        .expect_err("hashing should fail");
+    assert!(matches!(error, Error::Sqlite { .. }));
+    assert_eq!(error.object(), &Object::Table("b".to_owned()));
+}
+
+/// Change the schema through another connection when a table is visited.
+struct SchemaChanger(Connection);
+
+impl HashSink for SchemaChanger {
+    fn begin_table(&mut self, _name: &[u8]) {
+        self.0
+            .execute_batch("CREATE TABLE IF NOT EXISTS c (intval INT);")
+            .expect("failed to change schema");
+    }
+
+    fn value(&mut self, _value: ValueRef<'_>) {}
+}
+
+#[test]
+pub fn test_error_schema_changed() {
+    let db_file = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("test_error_schema_changed.db");
+    let _ = fs::remove_file(&db_file);
+
+    let conn = Connection::open(&db_file).expect("failed to open database");
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))
+        .expect("failed to enable WAL");
+    conn.execute_batch(
+        "
+        CREATE TABLE a (intval INT);
+        INSERT INTO a VALUES (1);
+        ",
+    )
+    .expect("failed to populate database");
+
+    let mut sink = SchemaChanger(Connection::open(&db_file).expect("failed to open database"));
+    let result = HashOptions::new().visit(&conn, &mut sink);
+    assert!(matches!(result, Err(Error::SchemaChanged { .. })));
+}
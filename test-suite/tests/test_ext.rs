@@ -0,0 +1,64 @@
+use rusqlite::Connection;
+use sqlite_dbhash::{ConnectionExt, HashOptions, Selection, dbhash};
+
+#[test]
+pub fn test_ext_matches_dbhash() {
+    let conn = Connection::open_in_memory().expect("failed to open database");
+    conn.execute_batch(
+        "
+        CREATE TABLE t1 (intval INT);
+        CREATE TABLE t2 (textval TEXT);
+        INSERT INTO t1 VALUES (1), (2);
+        INSERT INTO t2 VALUES ('a');
+        ",
+    )
+    .expect("failed to populate database");
+
+    for (ext, selection) in [
+        (conn.dbhash(), Selection::SchemaAndContent),
+        (conn.schema_hash(), Selection::SchemaOnly),
+        (conn.content_hash(), Selection::ContentOnly),
+    ] {
+        assert_eq!(
+            ext.expect("failed to hash"),
+            dbhash(&conn, None, selection).expect("failed to hash")
+        );
+    }
+    assert_eq!(
+        conn.table_hash("t1").expect("failed to hash"),
+        HashOptions::new()
+            .table_name("t1")
+            .hash(&conn)
+            .expect("failed to hash")
+    );
+}
+
+#[test]
+pub fn test_ext_uncommitted_changes() {
+    let mut conn = Connection::open_in_memory().expect("failed to open database");
+    conn.execute_batch(
+        "
+        CREATE TABLE t (intval INT);
+        INSERT INTO t VALUES (1), (2);
+        ",
+    )
+    .expect("failed to populate database");
+    let before = conn.dbhash().expect("failed to hash");
+
+    let mut tx = conn.transaction().expect("failed to start transaction");
+    tx.execute("INSERT INTO t VALUES (3)", [])
+        .expect("failed to insert");
+    let in_tx = tx.dbhash().expect("failed to hash");
+    assert_ne!(in_tx, before);
+
+    let sp = tx.savepoint().expect("failed to create savepoint");
+    sp.execute("DELETE FROM t WHERE intval = 3", [])
+        .expect("failed to delete");
+    assert_eq!(sp.dbhash().expect("failed to hash"), before);
+    // Dropping the savepoint rolls it back
+    drop(sp);
+
+    assert_eq!(tx.dbhash().expect("failed to hash"), in_tx);
+    tx.rollback().expect("failed to roll back");
+    assert_eq!(conn.dbhash().expect("failed to hash"), before);
+}
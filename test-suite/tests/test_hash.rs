@@ -0,0 +1,82 @@
+use rusqlite::Connection;
+use sha2::{Digest, Sha256};
+use sqlite_dbhash::{DbHash, HashOptions, ParseDbHashError, Selection, Sha1, dbhash, dbhash_with};
+
+// SHA1 of empty input, which is also the hash of an empty database.
+const EMPTY_HASH: &str = "da39a3ee5e6b4b0d3255bfef95601890afd80709";
+
+#[test]
+pub fn test_hash_format_and_parse() {
+    let conn = Connection::open_in_memory().expect("failed to open database");
+    let hash = dbhash(&conn, None, Selection::SchemaAndContent).expect("failed to hash");
+
+    assert_eq!(hash.to_string(), EMPTY_HASH);
+    assert_eq!(format!("{hash:X}"), EMPTY_HASH.to_ascii_uppercase());
+    assert_eq!(hash.to_base64(), "2jmj7l5rSw0yVb/vlWAYkK/YBwk=");
+    assert_eq!(EMPTY_HASH.parse::<DbHash>(), Ok(hash));
+    assert_eq!(EMPTY_HASH.to_ascii_uppercase().parse::<DbHash>(), Ok(hash));
+    assert_ne!(DbHash::from_bytes([0; 20]), hash);
+
+    assert_eq!(
+        "da39".parse::<DbHash>(),
+        Err(ParseDbHashError::InvalidLength {
+            expected: 40,
+            found: 4
+        })
+    );
+    assert_eq!(
+        "da39a3ee5e6b4b0d3255bfef95601890afd8070é".parse::<DbHash>(),
+        Err(ParseDbHashError::InvalidCharacter {
+            character: 'é',
+            index: 39
+        })
+    );
+}
+
+#[test]
+pub fn test_hash_serde() {
+    let hash: DbHash = EMPTY_HASH.parse().expect("failed to parse hash");
+
+    let json = serde_json::to_string(&hash).expect("failed to serialize hash");
+    assert_eq!(json, format!("\"{EMPTY_HASH}\""));
+    assert_eq!(
+        serde_json::from_str::<DbHash>(&json).expect("failed to deserialize hash"),
+        hash
+    );
+}
+
+#[test]
+pub fn test_hash_with_digest() {
+    let conn = Connection::open_in_memory().expect("failed to open database");
+    conn.execute_batch(
+        "
+        CREATE TABLE t (intval INT, textval TEXT);
+        INSERT INTO t VALUES (1, 'a'), (2, NULL);
+        ",
+    )
+    .expect("failed to populate database");
+
+    let options = HashOptions::new();
+    assert_eq!(
+        options.hash_with::<Sha1>(&conn).expect("failed to hash"),
+        options.hash(&conn).expect("failed to hash")
+    );
+
+    // The same byte stream is fed to any digest.
+    let stream = b"1\x00\x00\x00\x00\x00\x00\x00\x013a1\x00\x00\x00\x00\x00\x00\x00\x020";
+    assert_eq!(
+        HashOptions::new()
+            .selection(Selection::ContentOnly)
+            .hash_with::<Sha256>(&conn)
+            .expect("failed to hash")
+            .into_output(),
+        Sha256::digest(stream)
+    );
+    assert_eq!(
+        dbhash_with::<Sha256>(&conn, Some("t"), Selection::ContentOnly)
+            .expect("failed to hash")
+            .to_string()
+            .len(),
+        64
+    );
+}
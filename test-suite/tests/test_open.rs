@@ -0,0 +1,153 @@
+use std::{env, fs, path::PathBuf};
+
+use rusqlite::Connection;
+use sqlite_dbhash::{
+    ConnectionExt, Error, HashOptions, Object, OpenOptions, Selection, dbhash_bytes, dbhash_path,
+};
+
+/// Create a fresh path for a test database named `name`.
+fn db_path(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("test_open");
+    fs::create_dir_all(&dir).expect("failed to create directory");
+    let path = dir.join(name);
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[test]
+pub fn test_open_hash_path() {
+    // Characters that need escaping in a URI
+    let path = db_path("test open?#%.db");
+    let conn = Connection::open(&path).expect("failed to open database");
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))
+        .expect("failed to enable WAL");
+    conn.execute_batch(
+        "
+        CREATE TABLE t (intval INT, textval TEXT);
+        INSERT INTO t VALUES (1, 'a'), (2, 'b');
+        ",
+    )
+    .expect("failed to populate database");
+    let expected = conn.dbhash().expect("failed to hash");
+    conn.close().expect("failed to close database");
+
+    // Immutable files are read without creating the WAL sidecars
+    let sidecars = ["-wal", "-shm"].map(|suffix| {
+        let mut sidecar = path.clone().into_os_string();
+        sidecar.push(suffix);
+        PathBuf::from(sidecar)
+    });
+    for sidecar in &sidecars {
+        let _ = fs::remove_file(sidecar);
+    }
+    assert_eq!(
+        dbhash_path(&path, &OpenOptions::new().immutable(true)).expect("failed to hash"),
+        expected
+    );
+    for sidecar in &sidecars {
+        assert!(!sidecar.exists(), "{} was created", sidecar.display());
+    }
+    assert_eq!(
+        dbhash_path(&path, &OpenOptions::new()).expect("failed to hash"),
+        expected
+    );
+    assert_eq!(
+        HashOptions::new()
+            .selection(Selection::ContentOnly)
+            .hash_path(&path, &OpenOptions::new())
+            .expect("failed to hash"),
+        HashOptions::new()
+            .selection(Selection::ContentOnly)
+            .hash(&Connection::open(&path).expect("failed to open database"))
+            .expect("failed to hash")
+    );
+}
+
+#[test]
+pub fn test_open_missing_file() {
+    let path = db_path("missing.db");
+
+    let result = dbhash_path(&path, &OpenOptions::new());
+    assert!(matches!(
+        result,
+        Err(Error::NotFound { object: Object::File(p) }) if p == path
+    ));
+    assert!(!path.exists());
+}
+
+#[test]
+pub fn test_open_not_a_database() {
+    let path = db_path("not_a_database.db");
+    fs::write(
+        &path,
+        b"definitely not a sqlite database, but long enough to have a header",
+    )
+    .expect("failed to write file");
+
+    let result = dbhash_path(&path, &OpenOptions::new());
+    assert!(matches!(
+        result,
+        Err(Error::NotADatabase { object: Object::File(p) }) if p == path
+    ));
+}
+
+#[test]
+pub fn test_open_hash_bytes() {
+    let path = db_path("image.db");
+    let conn = Connection::open(&path).expect("failed to open database");
+    conn.execute_batch(
+        "
+        CREATE TABLE t (intval INT, blobval BLOB);
+        INSERT INTO t VALUES (1, x'00'), (2, x'0102');
+        CREATE INDEX idx_t ON t (blobval);
+        ",
+    )
+    .expect("failed to populate database");
+    let expected = conn.dbhash().expect("failed to hash");
+    conn.close().expect("failed to close database");
+
+    let image = fs::read(&path).expect("failed to read database");
+    assert_eq!(dbhash_bytes(&image).expect("failed to hash"), expected);
+    assert_eq!(
+        HashOptions::new()
+            .table_pattern("t")
+            .selection(Selection::SchemaOnly)
+            .hash_bytes(&image)
+            .expect("failed to hash"),
+        HashOptions::new()
+            .table_pattern("t")
+            .selection(Selection::SchemaOnly)
+            .hash_path(&path, &OpenOptions::new())
+            .expect("failed to hash"),
+    );
+
+    assert_eq!(
+        dbhash_bytes(&[]).expect("failed to hash"),
+        Connection::open_in_memory()
+            .expect("failed to open database")
+            .dbhash()
+            .expect("failed to hash")
+    );
+
+    // Images of WAL databases can be hashed too, once checkpointed
+    let path = db_path("wal_image.db");
+    let conn = Connection::open(&path).expect("failed to open database");
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))
+        .expect("failed to enable WAL");
+    conn.execute_batch("CREATE TABLE t (x INT); INSERT INTO t VALUES (1);")
+        .expect("failed to populate database");
+    conn.close().expect("failed to close database");
+    let image = fs::read(&path).expect("failed to read database");
+    assert_eq!(image[18..20], [2, 2]);
+    assert_eq!(
+        dbhash_bytes(&image).expect("failed to hash"),
+        dbhash_path(&path, &OpenOptions::new()).expect("failed to hash")
+    );
+
+    assert!(matches!(
+        dbhash_bytes(&[0xAA; 4096]),
+        Err(Error::NotADatabase {
+            object: Object::Image
+        })
+    ));
+}
@@ -0,0 +1,68 @@
+use rusqlite::Connection;
+use sqlite_dbhash::{HashOptions, RowOrder, Selection, hash_sql, hash_statement};
+
+fn populated_db() -> Connection {
+    let conn = Connection::open_in_memory().expect("failed to open database");
+    conn.execute_batch(
+        "
+        CREATE TABLE t (id INT, textval TEXT, realval REAL);
+        INSERT INTO t
+        VALUES
+            (1, 'a', 0.5),
+            (2, NULL, -1e999),
+            (3, 'áÁñçéá', 3.14),
+            (3, 'áÁñçéá', 3.14);
+        ",
+    )
+    .expect("failed to populate database");
+    conn
+}
+
+#[test]
+pub fn test_query_matches_content() {
+    let conn = populated_db();
+
+    assert_eq!(
+        hash_sql(&conn, r#"SELECT * FROM "t""#, [], RowOrder::Ordered)
+            .expect("failed to hash query"),
+        HashOptions::new()
+            .table_pattern("t")
+            .selection(Selection::ContentOnly)
+            .hash(&conn)
+            .expect("failed to hash database")
+    );
+}
+
+#[test]
+pub fn test_query_params_and_order() {
+    let conn = populated_db();
+
+    let ordered = |sql| hash_sql(&conn, sql, [2], RowOrder::Ordered).expect("failed to hash");
+    let unordered = |sql| hash_sql(&conn, sql, [2], RowOrder::Unordered).expect("failed to hash");
+
+    assert_eq!(
+        ordered("SELECT * FROM t WHERE id >= ?1 ORDER BY id"),
+        ordered("SELECT * FROM t WHERE NOT id < ?1 ORDER BY id")
+    );
+    assert_ne!(
+        ordered("SELECT * FROM t WHERE id >= ?1 ORDER BY id"),
+        ordered("SELECT * FROM t WHERE id >= ?1 ORDER BY id DESC")
+    );
+    assert_eq!(
+        unordered("SELECT * FROM t WHERE id >= ?1 ORDER BY id"),
+        unordered("SELECT * FROM t WHERE id >= ?1 ORDER BY id DESC")
+    );
+    // Duplicated rows still count
+    assert_ne!(
+        unordered("SELECT * FROM t WHERE id >= ?1"),
+        unordered("SELECT DISTINCT * FROM t WHERE id >= ?1")
+    );
+
+    let mut stmt = conn
+        .prepare("SELECT * FROM t WHERE id >= ?1 ORDER BY id DESC")
+        .expect("failed to prepare statement");
+    assert_eq!(
+        hash_statement(&mut stmt, [2], RowOrder::Unordered).expect("failed to hash"),
+        unordered("SELECT * FROM t WHERE id >= ?1")
+    );
+}
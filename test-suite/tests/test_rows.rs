@@ -0,0 +1,224 @@
+use rusqlite::{DatabaseName, types::Value};
+use sqlite_dbhash::{ConnectionExt, Error, HashOptions, Object, Selection, TenantPolicy};
+
+use crate::harness::open_with;
+
+mod harness;
+
+const ORDERS: &str = "
+    CREATE TABLE orders (id INT, created_at TEXT, note TEXT);
+    CREATE TABLE users (id INT, active INT);
+";
+
+#[test]
+pub fn test_rows_filter() {
+    let conn = open_with(&[
+        ORDERS,
+        "
+        INSERT INTO orders VALUES (1, '2025-12-31', 'old'), (2, '2026-01-01', 'new'), (3, '2026-02-01', 'x''y');
+        INSERT INTO users VALUES (1, 1), (2, 0);
+        ",
+    ]
+    .concat());
+
+    let expected = open_with(
+        &[
+            ORDERS,
+            "
+        INSERT INTO orders VALUES (2, '2026-01-01', 'new'), (3, '2026-02-01', 'x''y');
+        INSERT INTO users VALUES (1, 1);
+        ",
+        ]
+        .concat(),
+    )
+    .dbhash()
+    .expect("failed to hash");
+    assert_eq!(
+        HashOptions::new()
+            .row_filter("ORDERS", "created_at >= ?", ["2026-01-01".to_owned()])
+            .row_filter("users", "active", Vec::<Value>::new())
+            .hash(&conn)
+            .expect("failed to hash"),
+        expected
+    );
+
+    // Parameters are bound, not interpolated
+    let partial = open_with(
+        &[
+            ORDERS,
+            "INSERT INTO orders VALUES (1, '2025-12-31', 'old'), (3, '2026-02-01', 'x''y');",
+        ]
+        .concat(),
+    );
+    assert_eq!(
+        HashOptions::new()
+            .row_filter(
+                "orders",
+                "note = ? OR id = ?",
+                [Value::from("x'y".to_owned()), Value::from(1)]
+            )
+            .table_name("orders")
+            .hash(&conn)
+            .expect("failed to hash"),
+        partial.table_hash("orders").expect("failed to hash")
+    );
+
+    // A later filter on the same table replaces the earlier one
+    assert_eq!(
+        HashOptions::new()
+            .row_filter("orders", "0", Vec::<Value>::new())
+            .row_filter("Orders", "created_at >= ?", ["2026-01-01".to_owned()])
+            .row_filter("users", "active", Vec::<Value>::new())
+            .hash(&conn)
+            .expect("failed to hash"),
+        expected
+    );
+}
+
+#[test]
+pub fn test_rows_filter_invalid() {
+    let conn = open_with(ORDERS);
+    let result = HashOptions::new()
+        .row_filter("orders", "no_such_column = ?", [1])
+        .hash(&conn);
+    assert!(matches!(
+        result,
+        Err(Error::Sqlite { object: Object::Table(name), .. }) if name == "orders"
+    ));
+
+    // A filter on a missing table is not silently ignored
+    let result = HashOptions::new()
+        .row_filter("order", "id > ?", [1])
+        .hash(&conn);
+    assert!(matches!(
+        result,
+        Err(Error::NotFound { object: Object::Table(name) }) if name == "order"
+    ));
+    // but does not matter if only the schema is hashed
+    assert_eq!(
+        HashOptions::new()
+            .row_filter("order", "id > ?", [1])
+            .selection(Selection::SchemaOnly)
+            .hash(&conn)
+            .expect("failed to hash"),
+        conn.schema_hash().expect("failed to hash")
+    );
+}
+
+const TENANTS: &str = "
+    CREATE TABLE accounts (id INT, Tenant_Id INT, name TEXT);
+    CREATE TABLE invoices (id INT, tenant_id INT, amount REAL);
+    CREATE TABLE currencies (code TEXT);
+    INSERT INTO currencies VALUES ('EUR'), ('USD');
+";
+
+#[test]
+pub fn test_rows_tenant() {
+    let conn = open_with(
+        &[
+            TENANTS,
+            "
+        INSERT INTO accounts VALUES (1, 7, 'alice'), (2, 8, 'bob');
+        INSERT INTO invoices VALUES (1, 7, 1.5), (2, 8, 2.5), (3, 7, 3.5);
+        ",
+        ]
+        .concat(),
+    );
+    let tenant = open_with(
+        &[
+            TENANTS,
+            "
+        INSERT INTO accounts VALUES (1, 7, 'alice');
+        INSERT INTO invoices VALUES (1, 7, 1.5), (3, 7, 3.5);
+        ",
+        ]
+        .concat(),
+    );
+
+    assert_eq!(
+        HashOptions::new()
+            .tenant("tenant_id", 7, TenantPolicy::IncludeTable)
+            .hash(&conn)
+            .expect("failed to hash"),
+        tenant.dbhash().expect("failed to hash")
+    );
+    assert_eq!(
+        HashOptions::new()
+            .tenant("TENANT_ID", 7, TenantPolicy::SkipTable)
+            .selection(Selection::ContentOnly)
+            .hash(&conn)
+            .expect("failed to hash"),
+        HashOptions::new()
+            .exclude_name("currencies")
+            .selection(Selection::ContentOnly)
+            .hash(&tenant)
+            .expect("failed to hash")
+    );
+    // Combined with a row filter
+    assert_eq!(
+        HashOptions::new()
+            .tenant("tenant_id", 7, TenantPolicy::SkipTable)
+            .row_filter("invoices", "amount > ?", [2.0])
+            .table_name("invoices")
+            .selection(Selection::ContentOnly)
+            .hash(&conn)
+            .expect("failed to hash"),
+        HashOptions::new()
+            .row_filter("invoices", "id = ?", [3])
+            .table_name("invoices")
+            .selection(Selection::ContentOnly)
+            .hash(&conn)
+            .expect("failed to hash")
+    );
+}
+
+#[test]
+pub fn test_rows_tenant_invalid_utf8_name() {
+    let conn = open_with(
+        "
+        CREATE TABLE t (intval INT, tenant_id TEXT);
+        INSERT INTO t VALUES (1, 'a'), (2, 'b'), (3, 'a');
+        ",
+    );
+    let expected = HashOptions::new()
+        .row_filter("t", "tenant_id = 'a'", Vec::<Value>::new())
+        .selection(Selection::ContentOnly)
+        .hash(&conn)
+        .expect("failed to hash");
+
+    // There is no way to write a name with invalid UTF-8 in SQL text
+    // through rusqlite, so rename the table by rewriting the schema.
+    conn.execute_batch(
+        r#"
+        PRAGMA writable_schema = ON;
+        UPDATE sqlite_schema
+           SET name = CAST(x'74ff' AS TEXT),
+               tbl_name = CAST(x'74ff' AS TEXT),
+               sql = replace(sql, ' t ', ' "' || CAST(x'74ff' AS TEXT) || '" ')
+         WHERE name = 't';
+        PRAGMA writable_schema = OFF;
+        "#,
+    )
+    .expect("failed to rename table");
+    // Hash a copy to load the rewritten schema
+    let image = conn
+        .serialize(DatabaseName::Main)
+        .expect("failed to serialize");
+
+    assert_eq!(
+        HashOptions::new()
+            .tenant("tenant_id", "a".to_owned(), TenantPolicy::SkipTable)
+            .selection(Selection::ContentOnly)
+            .hash_bytes(&image)
+            .expect("failed to hash"),
+        expected
+    );
+    assert_ne!(
+        HashOptions::new()
+            .tenant("tenant_id", "b".to_owned(), TenantPolicy::SkipTable)
+            .selection(Selection::ContentOnly)
+            .hash_bytes(&image)
+            .expect("failed to hash"),
+        expected
+    );
+}
@@ -0,0 +1,557 @@
+use rusqlite::{Connection, types::ValueRef};
+use sqlite_dbhash::{
+    ConnectionExt, Error, HashOptions, HashSink, Object, RowOrder, Selection, TableMatcher,
+    hash_sql, regex::bytes::Regex,
+};
+
+use crate::harness::open_with;
+
+mod harness;
+
+const USER_ROLES: &str = "
+    CREATE TABLE user_roles (user INT, role TEXT);
+    CREATE INDEX idx_user_roles ON user_roles (role);
+    INSERT INTO user_roles VALUES (1, 'admin'), (2, 'guest');
+";
+
+const USER_X_ROLES: &str = "
+    CREATE TABLE userXroles (intval INT);
+    INSERT INTO userXroles VALUES (1);
+";
+
+const USERS: &str = "
+    CREATE TABLE users (id INT, name TEXT);
+    INSERT INTO users VALUES (1, 'alice'), (2, 'bob');
+";
+
+#[test]
+pub fn test_select_exact_name() {
+    let conn = open_with(&[USER_ROLES, USER_X_ROLES, USERS].concat());
+    let only = open_with(USER_ROLES);
+
+    for selection in [
+        Selection::SchemaAndContent,
+        Selection::SchemaOnly,
+        Selection::ContentOnly,
+    ] {
+        let expected = HashOptions::new()
+            .selection(selection)
+            .hash(&only)
+            .expect("failed to hash");
+        assert_eq!(
+            HashOptions::new()
+                .table_name("USER_ROLES")
+                .selection(selection)
+                .hash(&conn)
+                .expect("failed to hash"),
+            expected
+        );
+        // The LIKE pattern also matches `userXroles`
+        assert_ne!(
+            HashOptions::new()
+                .table_pattern("user_roles")
+                .selection(selection)
+                .hash(&conn)
+                .expect("failed to hash"),
+            expected
+        );
+    }
+    assert_eq!(
+        conn.table_hash("User_Roles").expect("failed to hash"),
+        only.dbhash().expect("failed to hash")
+    );
+}
+
+#[test]
+pub fn test_select_union() {
+    let conn = open_with(&[USER_ROLES, USER_X_ROLES, USERS].concat());
+    let expected = open_with(&[USER_ROLES, USERS].concat())
+        .dbhash()
+        .expect("failed to hash");
+
+    assert_eq!(
+        HashOptions::new()
+            .table_names(["users", "user_roles"])
+            .hash(&conn)
+            .expect("failed to hash"),
+        expected
+    );
+    assert_eq!(
+        HashOptions::new()
+            .table_pattern("users%")
+            .table_name("user_roles")
+            .hash(&conn)
+            .expect("failed to hash"),
+        expected
+    );
+    assert_eq!(
+        HashOptions::new()
+            .table_names(["missing", "user_roles"])
+            .hash(&conn)
+            .expect("failed to hash"),
+        conn.table_hash("user_roles").expect("failed to hash")
+    );
+}
+
+#[test]
+pub fn test_select_exclude() {
+    let conn = open_with(
+        "
+        CREATE TABLE users (id INT, name TEXT);
+        CREATE TABLE audit_log (id INT, entry TEXT);
+        CREATE INDEX idx_audit_log ON audit_log (entry);
+        CREATE TABLE Cache_pages (key TEXT, value BLOB);
+        CREATE TABLE cache_rows (key TEXT, value BLOB);
+        INSERT INTO users VALUES (1, 'alice');
+        INSERT INTO audit_log VALUES (1, 'created alice');
+        INSERT INTO Cache_pages VALUES ('a', x'00');
+        INSERT INTO cache_rows VALUES ('b', x'01');
+        ",
+    );
+    let users = "
+        CREATE TABLE users (id INT, name TEXT);
+        INSERT INTO users VALUES (1, 'alice');
+    ";
+
+    assert_eq!(
+        HashOptions::new()
+            .exclude_pattern("audit_%")
+            .exclude_pattern("cache_%")
+            .hash(&conn)
+            .expect("failed to hash"),
+        open_with(users).dbhash().expect("failed to hash")
+    );
+    // GLOB is case-sensitive, so `Cache_pages` is kept
+    assert_eq!(
+        HashOptions::new()
+            .exclude_name("AUDIT_LOG")
+            .exclude_glob("cache_*")
+            .hash(&conn)
+            .expect("failed to hash"),
+        open_with(
+            &[
+                users,
+                "
+                CREATE TABLE Cache_pages (key TEXT, value BLOB);
+                INSERT INTO Cache_pages VALUES ('a', x'00');
+                ",
+            ]
+            .concat()
+        )
+        .dbhash()
+        .expect("failed to hash")
+    );
+    // Exclusions take precedence over inclusions
+    assert_eq!(
+        HashOptions::new()
+            .table_pattern("%s")
+            .exclude_name("cache_rows")
+            .selection(Selection::SchemaOnly)
+            .hash(&conn)
+            .expect("failed to hash"),
+        open_with(&[users, "CREATE TABLE Cache_pages (key TEXT, value BLOB);",].concat())
+            .schema_hash()
+            .expect("failed to hash")
+    );
+}
+
+#[test]
+pub fn test_select_matchers() {
+    let conn = open_with(&[USER_ROLES, USER_X_ROLES, USERS].concat());
+    let hash = |options: HashOptions| options.hash(&conn).expect("failed to hash");
+    let user_roles = open_with(USER_ROLES).dbhash().expect("failed to hash");
+    let users = open_with(USERS).dbhash().expect("failed to hash");
+
+    assert_eq!(
+        hash(HashOptions::new().include(TableMatcher::LikeEscape("user\\_roles".into(), '\\'))),
+        user_roles
+    );
+    assert_eq!(
+        hash(HashOptions::new().include(TableMatcher::Glob("user[_]*".into()))),
+        user_roles
+    );
+    // GLOB is case-sensitive
+    assert_eq!(
+        hash(HashOptions::new().include(TableMatcher::Glob("USER*".into()))),
+        Connection::open_in_memory()
+            .expect("failed to open database")
+            .dbhash()
+            .expect("failed to hash")
+    );
+    assert_eq!(
+        hash(HashOptions::new().include(TableMatcher::Regex(
+            Regex::new("^user_").expect("failed to compile regex")
+        ))),
+        user_roles
+    );
+    assert_eq!(
+        hash(
+            HashOptions::new()
+                .exclude(TableMatcher::Regex(
+                    Regex::new("roles$").expect("failed to compile regex")
+                ))
+                .selection(Selection::SchemaOnly)
+        ),
+        open_with(USERS).schema_hash().expect("failed to hash")
+    );
+    // Rules evaluated in Rust and by SQLite combine
+    assert_eq!(
+        hash(
+            HashOptions::new()
+                .include(TableMatcher::Regex(
+                    Regex::new("^users$").expect("failed to compile regex")
+                ))
+                .include(TableMatcher::Exact("USER_ROLES".into()))
+        ),
+        open_with(&[USER_ROLES, USERS].concat())
+            .dbhash()
+            .expect("failed to hash")
+    );
+    assert_eq!(
+        hash(
+            HashOptions::new()
+                .include(TableMatcher::Like(b"user%".to_vec()))
+                .exclude(TableMatcher::Regex(
+                    Regex::new("(?i)roles").expect("failed to compile regex")
+                ))
+        ),
+        users
+    );
+}
+
+#[test]
+pub fn test_select_schema_kinds() {
+    const SCHEMA: &str = "
+        CREATE TABLE t (intval INT, textval TEXT);
+        CREATE VIEW v AS SELECT intval FROM t;
+        CREATE TRIGGER tr AFTER INSERT ON t BEGIN SELECT 1; END;
+        INSERT INTO t VALUES (1, 'a');
+    ";
+    let conn = open_with(SCHEMA);
+    let indexed = open_with(&[SCHEMA, "CREATE INDEX idx_t ON t (textval);"].concat());
+    let hash = |conn: &Connection, selection| {
+        HashOptions::new()
+            .selection(selection)
+            .hash(conn)
+            .expect("failed to hash")
+    };
+
+    for selection in [
+        Selection::TABLES | Selection::VIEWS,
+        Selection::TRIGGERS,
+        Selection::SchemaAndContent - Selection::INDEXES,
+    ] {
+        assert_eq!(hash(&conn, selection), hash(&indexed, selection));
+    }
+    for selection in [
+        Selection::INDEXES,
+        Selection::TABLES | Selection::INDEXES,
+        Selection::SchemaOnly,
+    ] {
+        assert_ne!(hash(&conn, selection), hash(&indexed, selection));
+    }
+
+    // Only the tables contribute
+    assert_eq!(
+        hash(&conn, Selection::TABLES),
+        open_with("CREATE TABLE t (intval INT, textval TEXT);")
+            .schema_hash()
+            .expect("failed to hash")
+    );
+    assert_eq!(
+        hash(&conn, Selection::empty()),
+        "da39a3ee5e6b4b0d3255bfef95601890afd80709"
+            .parse()
+            .expect("failed to parse hash")
+    );
+}
+
+#[test]
+pub fn test_select_presets() {
+    assert_eq!(Selection::default(), Selection::SchemaAndContent);
+    assert_eq!(
+        Selection::TABLES | Selection::INDEXES | Selection::VIEWS | Selection::TRIGGERS,
+        Selection::SchemaOnly
+    );
+    assert_eq!(
+        Selection::SchemaAndContent - Selection::SCHEMA,
+        Selection::ContentOnly
+    );
+    assert!(Selection::SchemaAndContent.contains(Selection::SchemaOnly));
+    assert!(!Selection::ContentOnly.intersects(Selection::SCHEMA));
+    assert_eq!(
+        format!("{:?}", Selection::CONTENT | Selection::VIEWS),
+        "Selection(CONTENT | VIEWS)"
+    );
+    assert_eq!(format!("{:?}", Selection::empty()), "Selection(empty)");
+}
+
+#[test]
+pub fn test_select_foreign_key_closure() {
+    const RELATED: &str = "
+        CREATE TABLE regions (id INTEGER PRIMARY KEY, name TEXT);
+        CREATE TABLE customers (id INTEGER PRIMARY KEY, region INT REFERENCES Regions (id));
+        CREATE TABLE orders (id INTEGER PRIMARY KEY, customer INT REFERENCES customers (id));
+        CREATE INDEX idx_orders ON orders (customer);
+        CREATE TABLE order_items (id INTEGER PRIMARY KEY, \"order\" INT REFERENCES orders (id));
+        CREATE TRIGGER tr_items AFTER INSERT ON order_items BEGIN SELECT 1; END;
+        INSERT INTO regions VALUES (1, 'north');
+        INSERT INTO customers VALUES (1, 1);
+        INSERT INTO orders VALUES (1, 1);
+        INSERT INTO order_items VALUES (1, 1);
+    ";
+    const UNRELATED: &str = "
+        CREATE TABLE products (id INTEGER PRIMARY KEY, name TEXT);
+        CREATE TABLE prices (product INT REFERENCES products (id), price REAL);
+        INSERT INTO products VALUES (1, 'widget');
+        INSERT INTO prices VALUES (1, 9.99);
+    ";
+    let conn = open_with(&[RELATED, UNRELATED].concat());
+    let expected = open_with(RELATED).dbhash().expect("failed to hash");
+
+    for root in ["customers", "ORDER_ITEMS", "regions"] {
+        assert_eq!(
+            HashOptions::new()
+                .foreign_key_closure(root)
+                .hash(&conn)
+                .expect("failed to hash"),
+            expected
+        );
+    }
+    assert_eq!(
+        HashOptions::new()
+            .foreign_key_closure("customers")
+            .foreign_key_closure("prices")
+            .hash(&conn)
+            .expect("failed to hash"),
+        conn.dbhash().expect("failed to hash")
+    );
+    assert_eq!(
+        HashOptions::new()
+            .foreign_key_closure("products")
+            .table_name("regions")
+            .selection(Selection::SchemaOnly)
+            .hash(&conn)
+            .expect("failed to hash"),
+        HashOptions::new()
+            .table_names(["products", "prices", "regions"])
+            .selection(Selection::SchemaOnly)
+            .hash(&conn)
+            .expect("failed to hash")
+    );
+
+    let result = HashOptions::new()
+        .foreign_key_closure("missing")
+        .hash(&conn);
+    assert!(matches!(
+        result,
+        Err(Error::NotFound { object: Object::Table(name) }) if name == "missing"
+    ));
+}
+
+#[test]
+pub fn test_select_view_content() {
+    const SCHEMA: &str = "
+        CREATE TABLE t (intval INT, textval TEXT);
+        CREATE VIEW v AS SELECT textval, intval * 2 AS doubled FROM t WHERE intval > 0;
+    ";
+    let hash = |conn: &Connection, enabled| {
+        HashOptions::new()
+            .view_content(enabled)
+            .hash(conn)
+            .expect("failed to hash")
+    };
+    let conn = open_with(&[SCHEMA, "INSERT INTO t VALUES (0, 'z'), (1, 'a'), (2, 'b');"].concat());
+    // The same rows in a different physical order
+    let reordered =
+        open_with(&[SCHEMA, "INSERT INTO t VALUES (2, 'b'), (1, 'a'), (0, 'z');"].concat());
+
+    assert_eq!(hash(&conn, false), conn.dbhash().expect("failed to hash"));
+    assert_ne!(hash(&conn, true), hash(&conn, false));
+    assert_eq!(
+        HashOptions::new()
+            .view_content(true)
+            .table_name("v")
+            .selection(Selection::ContentOnly)
+            .hash(&conn)
+            .expect("failed to hash"),
+        hash_sql(
+            &conn,
+            "SELECT 'a', 2 UNION ALL SELECT 'b', 4",
+            [],
+            RowOrder::Ordered
+        )
+        .expect("failed to hash")
+    );
+    assert_eq!(
+        HashOptions::new()
+            .view_content(true)
+            .table_name("v")
+            .selection(Selection::ContentOnly)
+            .hash(&reordered)
+            .expect("failed to hash"),
+        HashOptions::new()
+            .view_content(true)
+            .table_name("v")
+            .selection(Selection::ContentOnly)
+            .hash(&conn)
+            .expect("failed to hash")
+    );
+
+    // The view follows the underlying data
+    conn.execute("UPDATE t SET intval = 0 WHERE intval = 1", [])
+        .expect("failed to update");
+    let filtered = HashOptions::new()
+        .view_content(true)
+        .table_name("v")
+        .selection(Selection::ContentOnly);
+    assert_eq!(
+        filtered.hash(&conn).expect("failed to hash"),
+        hash_sql(&conn, "SELECT 'b', 4", [], RowOrder::Ordered).expect("failed to hash")
+    );
+
+    // Errors in views are reported as such
+    conn.execute_batch("DROP TABLE t; CREATE TABLE t (other INT);")
+        .expect("failed to replace table");
+    assert!(matches!(
+        filtered.hash(&conn),
+        Err(Error::Sqlite { object: Object::View(name), .. }) if name == "v"
+    ));
+}
+
+#[test]
+pub fn test_select_virtual_table_content() {
+    const SCHEMA: &str = "
+        CREATE TABLE notes (id INT);
+        CREATE VIRTUAL TABLE docs USING fts5 (title, body);
+        INSERT INTO notes VALUES (1);
+    ";
+    let rows = [
+        "('a', 'first doc')",
+        "('b', 'second doc')",
+        "('c', 'third')",
+    ];
+    // One segment per insert
+    let fragmented = open_with(SCHEMA);
+    for row in rows {
+        fragmented
+            .execute(&format!("INSERT INTO docs VALUES {row}"), [])
+            .expect("failed to insert");
+    }
+    // The same content in a single merged segment, inserted in another order
+    let merged = open_with(SCHEMA);
+    merged
+        .execute_batch(
+            "
+            INSERT INTO docs (rowid, title, body) VALUES (3, 'c', 'third');
+            INSERT INTO docs (rowid, title, body) VALUES (1, 'a', 'first doc'), (2, 'b', 'second doc');
+            INSERT INTO docs (docs) VALUES ('optimize');
+            "
+        )
+        .expect("failed to populate database");
+
+    let hash = |conn: &Connection, enabled| {
+        HashOptions::new()
+            .virtual_table_content(enabled)
+            .hash(conn)
+            .expect("failed to hash")
+    };
+    assert_ne!(hash(&fragmented, false), hash(&merged, false));
+    assert_eq!(hash(&fragmented, true), hash(&merged, true));
+    assert_ne!(hash(&fragmented, true), hash(&fragmented, false));
+
+    // Only the virtual table itself is hashed, not its shadow tables
+    assert_eq!(
+        HashOptions::new()
+            .virtual_table_content(true)
+            .table_pattern("docs%")
+            .selection(Selection::ContentOnly)
+            .hash(&merged)
+            .expect("failed to hash"),
+        hash_sql(
+            &merged,
+            "SELECT 'a', 'first doc' UNION ALL SELECT 'b', 'second doc' UNION ALL SELECT 'c', 'third'",
+            [],
+            RowOrder::Ordered
+        )
+        .expect("failed to hash")
+    );
+
+    // Changes to the logical content are noticed
+    merged
+        .execute("DELETE FROM docs WHERE title = 'b'", [])
+        .expect("failed to delete");
+    assert_ne!(hash(&fragmented, true), hash(&merged, true));
+}
+
+/// Record the names of the tables hashed.
+#[derive(Default)]
+struct TableNames(Vec<String>);
+
+impl HashSink for TableNames {
+    fn begin_table(&mut self, name: &[u8]) {
+        self.0.push(String::from_utf8_lossy(name).into_owned());
+    }
+
+    fn value(&mut self, _value: ValueRef<'_>) {}
+}
+
+#[test]
+pub fn test_select_internal_tables() {
+    const SCHEMA: &str = "
+        CREATE TABLE b (id INTEGER PRIMARY KEY AUTOINCREMENT, textval TEXT);
+        CREATE TABLE a (intval INT);
+        CREATE INDEX idx_a ON a (intval);
+        CREATE VIEW v AS SELECT * FROM a;
+        INSERT INTO a VALUES (1), (2), (2);
+        INSERT INTO b (textval) VALUES ('x'), ('y');
+    ";
+    let conn = open_with(SCHEMA);
+    // The same rows, but the next id differs
+    let replica = open_with(
+        &[
+            SCHEMA,
+            "INSERT INTO b (textval) VALUES ('z'); DELETE FROM b WHERE textval = 'z';",
+        ]
+        .concat(),
+    );
+
+    assert_eq!(
+        conn.dbhash().expect("failed to hash"),
+        replica.dbhash().expect("failed to hash")
+    );
+    let with_sequence = HashOptions::new().sequence_content(true);
+    assert_ne!(
+        with_sequence.hash(&conn).expect("failed to hash"),
+        with_sequence.hash(&replica).expect("failed to hash")
+    );
+
+    conn.execute_batch("ANALYZE").expect("failed to analyze");
+    let mut names = TableNames::default();
+    HashOptions::new()
+        .view_content(true)
+        .sequence_content(true)
+        .stat_content(true)
+        .visit(&conn, &mut names)
+        .expect("failed to visit");
+    let stats = names
+        .0
+        .iter()
+        .skip(4)
+        .all(|name| name.starts_with("sqlite_stat"));
+    assert_eq!(
+        names.0[..5],
+        ["a", "b", "v", "sqlite_sequence", "sqlite_stat1"]
+    );
+    assert!(stats);
+
+    // Internal tables are subject to the table selection
+    let mut names = TableNames::default();
+    HashOptions::new()
+        .sequence_content(true)
+        .stat_content(true)
+        .table_name("sqlite_stat1")
+        .visit(&conn, &mut names)
+        .expect("failed to visit");
+    assert_eq!(names.0, ["sqlite_stat1"]);
+}
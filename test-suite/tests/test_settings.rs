@@ -0,0 +1,150 @@
+use rusqlite::types::{Value, ValueRef};
+use sqlite_dbhash::{ConnectionExt, Error, HashOptions, HashSink, Object, Selection};
+
+use crate::harness::open_with;
+
+mod harness;
+
+const SCHEMA: &str = "CREATE TABLE t (x INT); INSERT INTO t VALUES (1);";
+
+#[test]
+pub fn test_settings_user_version() {
+    let old = open_with(SCHEMA);
+    let new = open_with(&[SCHEMA, "PRAGMA user_version = 2;"].concat());
+    assert_eq!(
+        old.dbhash().expect("failed to hash"),
+        new.dbhash().expect("failed to hash")
+    );
+
+    let options = HashOptions::new().settings(HashOptions::PERSISTENT_SETTINGS);
+    assert_ne!(
+        options.hash(&old).expect("failed to hash"),
+        options.hash(&new).expect("failed to hash")
+    );
+    old.pragma_update(None, "user_version", 2)
+        .expect("failed to set user_version");
+    assert_eq!(
+        options.hash(&old).expect("failed to hash"),
+        options.hash(&new).expect("failed to hash")
+    );
+
+    // Settings are part of the schema
+    let content = options.clone().selection(Selection::ContentOnly);
+    old.pragma_update(None, "application_id", 42)
+        .expect("failed to set application_id");
+    assert_eq!(
+        content.hash(&old).expect("failed to hash"),
+        new.content_hash().expect("failed to hash")
+    );
+    assert_ne!(
+        options.hash(&old).expect("failed to hash"),
+        options.hash(&new).expect("failed to hash")
+    );
+}
+
+#[test]
+pub fn test_settings_names() {
+    let conn = open_with(&[SCHEMA, "PRAGMA user_version = 3;"].concat());
+    // Names are matched ignoring case, and each setting is hashed once
+    assert_eq!(
+        HashOptions::new()
+            .setting("USER_VERSION")
+            .setting("user_version")
+            .hash(&conn)
+            .expect("failed to hash"),
+        HashOptions::new()
+            .setting("user_version")
+            .hash(&conn)
+            .expect("failed to hash")
+    );
+    assert!(matches!(
+        HashOptions::new().setting("user_versoin").hash(&conn),
+        Err(Error::NotFound { object: Object::Setting(name) }) if name == "user_versoin"
+    ));
+
+    // Pragmas that are not side-effect free settings are never run
+    for name in ["incremental_vacuum", "optimize", "integrity_check"] {
+        assert!(matches!(
+            HashOptions::new().setting(name).hash(&conn),
+            Err(Error::NotFound { object: Object::Setting(n) }) if n == name
+        ));
+    }
+}
+
+#[test]
+pub fn test_settings_attached() {
+    let conn = open_with(SCHEMA);
+    conn.execute_batch(
+        "
+        ATTACH DATABASE ':memory:' AS aux;
+        CREATE TABLE aux.t (x INT);
+        INSERT INTO aux.t VALUES (1);
+        PRAGMA aux.user_version = 5;
+        ",
+    )
+    .expect("failed to populate database");
+
+    let options = HashOptions::new().setting("user_version");
+    assert_eq!(
+        options
+            .clone()
+            .schema_name("aux")
+            .hash(&conn)
+            .expect("failed to hash"),
+        options
+            .hash(&open_with(&[SCHEMA, "PRAGMA user_version = 5;"].concat()))
+            .expect("failed to hash")
+    );
+    assert_eq!(
+        options.hash(&conn).expect("failed to hash"),
+        options.hash(&open_with(SCHEMA)).expect("failed to hash")
+    );
+}
+
+/// Record the settings visited.
+#[derive(Default)]
+struct Settings {
+    started: bool,
+    values: Vec<(String, Vec<Value>)>,
+}
+
+impl HashSink for Settings {
+    fn value(&mut self, value: ValueRef<'_>) {
+        if let Some((_, values)) = self.values.last_mut() {
+            values.push(value.into());
+        }
+    }
+
+    fn begin_settings(&mut self) {
+        self.started = true;
+    }
+
+    fn setting(&mut self, name: &str) {
+        assert!(self.started);
+        self.values.push((name.to_owned(), Vec::new()));
+    }
+}
+
+#[test]
+pub fn test_settings_sink() {
+    let conn = open_with(&[SCHEMA, "PRAGMA user_version = 7;"].concat());
+    let mut settings = Settings::default();
+    HashOptions::new()
+        .settings(["user_version", "Encoding"])
+        .visit(&conn, &mut settings)
+        .expect("failed to visit");
+    assert_eq!(
+        settings.values,
+        [
+            ("encoding".to_owned(), vec![Value::Text("UTF-8".to_owned())]),
+            ("user_version".to_owned(), vec![Value::Integer(7)]),
+        ]
+    );
+
+    // Without settings, the event is not emitted at all
+    let mut settings = Settings::default();
+    HashOptions::new()
+        .visit(&conn, &mut settings)
+        .expect("failed to visit");
+    assert!(!settings.started);
+}
@@ -116,3 +116,42 @@ pub fn test_single_table_with_indices() {
         ],
     );
 }
+
+// The stock dbhash only takes a non UTF-8 pattern as an argument on unix
+#[cfg(unix)]
+#[test]
+pub fn test_single_table_invalid_utf8_name() {
+    run_tests(
+        "test_single_table_invalid_utf8_name",
+        [
+            Step::sql(
+                "
+                CREATE TABLE t (intval INT, textval TEXT);
+                INSERT INTO t
+                VALUES
+                    (0, 'a'),
+                    (1, 'bbb');
+                CREATE INDEX idx_t ON t (textval);
+                ",
+            ),
+            // There is no way to write a name with invalid UTF-8 in SQL text
+            // through rusqlite, so rename the table by rewriting the schema.
+            Step::sql(
+                r#"
+                PRAGMA writable_schema = ON;
+                UPDATE sqlite_schema
+                   SET name = CAST(x'74ff22c1' AS TEXT)
+                 WHERE name = 't';
+                UPDATE sqlite_schema
+                   SET tbl_name = CAST(x'74ff22c1' AS TEXT),
+                       sql = replace(sql, ' t ', ' "' || CAST(x'74ff2222c1' AS TEXT) || '" ');
+                PRAGMA writable_schema = OFF;
+                "#,
+            ),
+            Step::compare_bytes(Some(b"t\xff\"\xc1"), Selection::SchemaAndContent),
+            Step::compare_bytes(Some(b"%\xff%"), Selection::SchemaAndContent),
+            Step::compare_bytes(Some(b"%\xc1"), Selection::ContentOnly),
+            Step::compare_bytes(Some(b"t%"), Selection::SchemaOnly),
+        ],
+    );
+}
@@ -0,0 +1,83 @@
+use rusqlite::{Connection, types::ValueRef};
+use sqlite_dbhash::{DigestSink, HashOptions, HashSink, Sha1};
+
+/// Record the events as strings while forwarding them to a digest.
+struct Recorder {
+    events: Vec<String>,
+    digest: DigestSink<Sha1>,
+}
+
+impl HashSink for Recorder {
+    fn begin_table(&mut self, name: &[u8]) {
+        self.events
+            .push(format!("table {}", String::from_utf8_lossy(name)));
+    }
+
+    fn begin_row(&mut self) {
+        self.events.push("row".to_owned());
+    }
+
+    fn value(&mut self, value: ValueRef<'_>) {
+        self.events.push(match value {
+            ValueRef::Null => "NULL".to_owned(),
+            ValueRef::Integer(value) => format!("INT {value}"),
+            ValueRef::Real(value) => format!("FLOAT {value}"),
+            ValueRef::Text(value) => format!("TEXT {}", String::from_utf8_lossy(value)),
+            ValueRef::Blob(value) => format!("BLOB {value:?}"),
+        });
+        self.digest.value(value);
+    }
+
+    fn begin_schema(&mut self) {
+        self.events.push("schema".to_owned());
+    }
+}
+
+#[test]
+pub fn test_sink_events() {
+    let conn = Connection::open_in_memory().expect("failed to open database");
+    conn.execute_batch(
+        "
+        CREATE TABLE b (intval INT);
+        CREATE TABLE A (textval TEXT);
+        INSERT INTO b VALUES (1), (2);
+        INSERT INTO A VALUES ('x');
+        ",
+    )
+    .expect("failed to populate database");
+
+    let mut recorder = Recorder {
+        events: Vec::new(),
+        digest: DigestSink::new(),
+    };
+    HashOptions::new()
+        .visit(&conn, &mut recorder)
+        .expect("failed to visit database");
+
+    assert_eq!(
+        recorder.events,
+        [
+            "table A",
+            "row",
+            "TEXT x",
+            "table b",
+            "row",
+            "INT 1",
+            "row",
+            "INT 2",
+            "schema",
+            "TEXT table",
+            "TEXT A",
+            "TEXT A",
+            "TEXT CREATE TABLE A (textval TEXT)",
+            "TEXT table",
+            "TEXT b",
+            "TEXT b",
+            "TEXT CREATE TABLE b (intval INT)",
+        ]
+    );
+    assert_eq!(
+        recorder.digest.finalize(),
+        HashOptions::new().hash(&conn).expect("failed to hash")
+    );
+}